@@ -0,0 +1,6 @@
+CREATE TABLE students (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    grade TEXT,
+    group_id INTEGER
+);
@@ -0,0 +1 @@
+ALTER TABLE attendance ADD COLUMN recorded_by TEXT;
@@ -0,0 +1 @@
+ALTER TABLE students ADD COLUMN address_zip TEXT;
@@ -0,0 +1 @@
+ALTER TABLE students ADD COLUMN birth_date TEXT;
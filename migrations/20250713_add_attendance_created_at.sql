@@ -0,0 +1,16 @@
+-- SQLite cannot ADD COLUMN with a non-constant default, so the table is rebuilt.
+-- Rows that predate this migration keep a NULL created_at since their entry time is unknown.
+CREATE TABLE attendance_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    student_id INTEGER NOT NULL,
+    date TEXT NOT NULL,
+    status TEXT NOT NULL,
+    recorded_by TEXT,
+    created_at TEXT DEFAULT (datetime('now'))
+);
+
+INSERT INTO attendance_new (id, student_id, date, status, recorded_by, created_at)
+SELECT id, student_id, date, status, recorded_by, NULL FROM attendance;
+
+DROP TABLE attendance;
+ALTER TABLE attendance_new RENAME TO attendance;
@@ -0,0 +1 @@
+ALTER TABLE students ADD COLUMN referral_source TEXT;
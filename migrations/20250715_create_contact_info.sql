@@ -0,0 +1,7 @@
+-- Parent/guardian contact details live apart from attendance data (FERPA).
+CREATE TABLE contact_info (
+    student_id INTEGER PRIMARY KEY REFERENCES students(id) ON DELETE CASCADE,
+    parent_name TEXT,
+    phone TEXT,
+    email TEXT
+);
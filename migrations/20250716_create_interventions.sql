@@ -0,0 +1,11 @@
+CREATE TABLE interventions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    student_id INTEGER NOT NULL REFERENCES students(id) ON DELETE CASCADE,
+    intervention_type TEXT NOT NULL,
+    notes TEXT,
+    outcome TEXT,
+    staff_id TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX idx_interventions_student ON interventions(student_id);
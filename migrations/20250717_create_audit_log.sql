@@ -0,0 +1,13 @@
+-- One row per change; old_value/new_value hold JSON snapshots of the affected record.
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    table_name TEXT NOT NULL,
+    record_id INTEGER NOT NULL,
+    action TEXT NOT NULL CHECK(action IN ('INSERT', 'UPDATE', 'DELETE')),
+    old_value TEXT,
+    new_value TEXT,
+    performed_by TEXT,
+    performed_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX idx_audit_log_record ON audit_log(table_name, record_id);
@@ -0,0 +1,8 @@
+CREATE TABLE scheduled_exports (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    cron TEXT NOT NULL,
+    format TEXT NOT NULL,
+    recipient TEXT NOT NULL,
+    last_run_at TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
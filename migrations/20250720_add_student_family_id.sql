@@ -0,0 +1,3 @@
+ALTER TABLE students ADD COLUMN family_id INTEGER;
+
+CREATE INDEX idx_students_family_id ON students (family_id);
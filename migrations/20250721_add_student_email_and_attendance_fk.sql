@@ -0,0 +1,24 @@
+ALTER TABLE students ADD COLUMN email TEXT;
+
+-- Attendance may reference students that were never enrolled; give them a placeholder row
+-- so the foreign key below holds for existing data.
+INSERT OR IGNORE INTO students (id, name)
+SELECT DISTINCT student_id, 'Student ' || student_id FROM attendance;
+
+-- SQLite cannot add a foreign key to an existing table, so attendance is rebuilt.
+CREATE TABLE attendance_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    student_id INTEGER NOT NULL REFERENCES students(id),
+    date TEXT NOT NULL,
+    status TEXT NOT NULL,
+    recorded_by TEXT,
+    created_at TEXT DEFAULT (datetime('now'))
+);
+
+INSERT INTO attendance_new (id, student_id, date, status, recorded_by, created_at)
+SELECT id, student_id, date, status, recorded_by, created_at FROM attendance;
+
+DROP TABLE attendance;
+ALTER TABLE attendance_new RENAME TO attendance;
+
+CREATE INDEX idx_attendance_student ON attendance(student_id);
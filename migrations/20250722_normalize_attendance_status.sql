@@ -0,0 +1,4 @@
+-- Statuses are validated case-insensitively from now on; store existing rows in canonical form.
+UPDATE attendance SET status = 'Present' WHERE lower(status) = 'present';
+UPDATE attendance SET status = 'Absent' WHERE lower(status) = 'absent';
+UPDATE attendance SET status = 'Late' WHERE lower(status) = 'late';
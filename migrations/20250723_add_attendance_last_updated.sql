@@ -0,0 +1,15 @@
+ALTER TABLE attendance ADD COLUMN last_updated TEXT;
+
+UPDATE attendance SET last_updated = created_at;
+
+-- SQLite cannot use datetime('now') as an ADD COLUMN default, so triggers keep the column current.
+CREATE TRIGGER attendance_last_updated_insert AFTER INSERT ON attendance
+BEGIN
+    UPDATE attendance SET last_updated = datetime('now') WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER attendance_last_updated_update
+AFTER UPDATE OF student_id, date, status, recorded_by ON attendance
+BEGIN
+    UPDATE attendance SET last_updated = datetime('now') WHERE id = NEW.id;
+END;
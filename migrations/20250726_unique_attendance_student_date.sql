@@ -0,0 +1,14 @@
+-- A student has at most one record per date. Existing duplicates keep the most recently
+-- entered row; the removed rows are written to the audit trail first.
+INSERT INTO audit_log (table_name, record_id, action, old_value, new_value, performed_by)
+SELECT 'attendance', id, 'DELETE',
+       json_object('student_id', student_id, 'date', date, 'status', status,
+                   'recorded_by', recorded_by),
+       NULL, 'migration:20250726_unique_attendance_student_date'
+FROM attendance
+WHERE id NOT IN (SELECT MAX(id) FROM attendance GROUP BY student_id, date);
+
+DELETE FROM attendance
+WHERE id NOT IN (SELECT MAX(id) FROM attendance GROUP BY student_id, date);
+
+CREATE UNIQUE INDEX idx_attendance_student_date ON attendance(student_id, date);
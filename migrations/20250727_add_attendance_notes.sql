@@ -0,0 +1,2 @@
+-- Free-text reason or comment for a record, e.g. why a student was absent.
+ALTER TABLE attendance ADD COLUMN notes TEXT DEFAULT NULL;
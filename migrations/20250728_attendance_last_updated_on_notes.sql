@@ -0,0 +1,8 @@
+-- Editing only the notes of a record should also bump last_updated.
+DROP TRIGGER attendance_last_updated_update;
+
+CREATE TRIGGER attendance_last_updated_update
+AFTER UPDATE OF student_id, date, status, recorded_by, notes ON attendance
+BEGIN
+    UPDATE attendance SET last_updated = datetime('now') WHERE id = NEW.id;
+END;
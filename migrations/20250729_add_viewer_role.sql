@@ -0,0 +1,12 @@
+-- SQLite cannot alter a CHECK constraint in place, so users is rebuilt to also allow 'viewer'.
+CREATE TABLE users_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL, -- Argon2 PHC string
+    role TEXT NOT NULL DEFAULT 'staff' CHECK (role IN ('admin', 'staff', 'viewer')),
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+INSERT INTO users_new (id, username, password_hash, role, created_at)
+    SELECT id, username, password_hash, role, created_at FROM users;
+DROP TABLE users;
+ALTER TABLE users_new RENAME TO users;
@@ -0,0 +1,8 @@
+-- Responses to POST /attendance requests that carried an Idempotency-Key header, replayed when the
+-- same key is sent again. Rows older than 24 hours are deleted by a background task.
+CREATE TABLE idempotency_keys (
+    key TEXT PRIMARY KEY,
+    response_status INTEGER NOT NULL,
+    response_body TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
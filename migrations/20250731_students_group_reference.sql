@@ -0,0 +1,20 @@
+-- Students may name groups that were never created; give them a placeholder row so every
+-- group_id resolves.
+INSERT OR IGNORE INTO groups (id, name)
+SELECT DISTINCT group_id, 'Group ' || group_id FROM students WHERE group_id IS NOT NULL;
+
+-- SQLite cannot add a foreign key to an existing table, and rebuilding students would disturb the
+-- tables that reference it, so group_id is checked by triggers instead of REFERENCES groups(id).
+CREATE TRIGGER students_group_id_insert
+BEFORE INSERT ON students
+WHEN NEW.group_id IS NOT NULL AND NOT EXISTS (SELECT 1 FROM groups WHERE id = NEW.group_id)
+BEGIN
+    SELECT RAISE(ABORT, 'FOREIGN KEY constraint failed');
+END;
+
+CREATE TRIGGER students_group_id_update
+BEFORE UPDATE OF group_id ON students
+WHEN NEW.group_id IS NOT NULL AND NOT EXISTS (SELECT 1 FROM groups WHERE id = NEW.group_id)
+BEGIN
+    SELECT RAISE(ABORT, 'FOREIGN KEY constraint failed');
+END;
@@ -0,0 +1,6 @@
+-- Programs that meet twice a day record attendance per session. Existing rows become morning
+-- records, and a student may now have one record per date and session.
+ALTER TABLE attendance ADD COLUMN session TEXT CHECK(session IN ('AM', 'PM')) DEFAULT 'AM';
+
+DROP INDEX idx_attendance_student_date;
+CREATE UNIQUE INDEX idx_attendance_student_date_session ON attendance(student_id, date, session);
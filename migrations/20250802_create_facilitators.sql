@@ -0,0 +1,12 @@
+-- Facilitators are the people attendance records are attributed to. A facilitator linked to a
+-- user account (by username) is credited with every record that account enters.
+CREATE TABLE facilitators (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    email TEXT,
+    username TEXT UNIQUE -- users.username of the linked account, if any
+);
+
+ALTER TABLE attendance ADD COLUMN facilitator_id INTEGER REFERENCES facilitators(id);
+
+CREATE INDEX idx_attendance_facilitator ON attendance(facilitator_id);
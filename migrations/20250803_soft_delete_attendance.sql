@@ -0,0 +1,11 @@
+-- Deleting a record now stamps deleted_at instead of removing the row, so removed records stay
+-- available for audits and can be restored.
+ALTER TABLE attendance ADD COLUMN deleted_at TEXT; -- NULL while the record is active
+
+-- A deleted record no longer occupies its student/date/session slot.
+DROP INDEX idx_attendance_student_date_session;
+CREATE UNIQUE INDEX idx_attendance_student_date_session
+    ON attendance(student_id, date, session) WHERE deleted_at IS NULL;
+
+-- Every read of attendance goes through this view so deleted records are left out.
+CREATE VIEW active_attendance AS SELECT * FROM attendance WHERE deleted_at IS NULL;
@@ -0,0 +1,9 @@
+-- Webhooks registered through POST /admin/webhooks. Each one receives a signed POST when its
+-- event fires; for "attendance.low" that is when a day's present rate drops below `threshold`.
+CREATE TABLE webhooks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL, -- HMAC-SHA256 key for X-YouthSync-Signature; never returned by the API
+    event TEXT NOT NULL,
+    threshold REAL NOT NULL
+);
@@ -2,179 +2,9736 @@
 // Provides endpoints to record attendance, generate daily attendance reports, and export data as CSV.
 
 use actix_cors::Cors;                  // Enable Cross-Origin Resource Sharing (CORS) for HTTP requests
-use actix_web::{App, HttpResponse, HttpServer, Responder, web}; // Actix Web framework components
-use chrono::{Datelike, NaiveDate};     // Date handling utilities
-use csv::Writer;                       // CSV writer for exporting records
+use actix_multipart::Multipart;        // multipart/form-data uploads for CSV import
+use actix_web::body::{BodyStream, BoxBody, MessageBody}; // Streamed, type-erased and middleware response bodies
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse}; // Types seen by middleware and extractors
+use actix_web::http::header::{HeaderName, HeaderValue}; // Building the X-Request-Id response header
+use actix_web::http::{Method, StatusCode, header}; // HTTP methods, status codes and header names
+use actix_web::{App, FromRequest, HttpMessage, HttpRequest, HttpResponse, HttpServer, middleware, web}; // Actix Web framework components
+use argon2::password_hash::SaltString; // Random salts for password hashing
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier}; // Password hashing for user accounts
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, Utc}; // Date handling utilities
+use croner::Cron;                      // Cron expression parsing for scheduled exports
+use csv::{Writer, WriterBuilder};      // CSV/TSV writers for exporting records
+use futures_util::future::BoxFuture;  // Futures returned by AttendanceRepository methods
+use futures_util::{Stream, TryStreamExt}; // Row streams from sqlx and streamed response bodies
+use hmac::{Hmac, Mac};                 // Signing webhook payloads
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation}; // JWT signing and verification
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart, header::ContentType}; // Scheduled export emails
+use lettre::transport::smtp::authentication::Credentials; // SMTP login
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor}; // Sending mail through SMTP_HOST
+use lru::LruCache;                     // Bounded cache of GET /report responses
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder}; // Prometheus metrics for GET /metrics
+use rand::RngCore;                     // Filling byte buffers for generated API keys
+use rand::rngs::OsRng;                 // Cryptographically secure randomness for salts, secrets and keys
 use serde::{Deserialize, Serialize};   // Serialization / deserialization for JSON and CSV
-use sqlx::{FromRow, SqlitePool};       // Async SQLite DB pool and mapping from query rows
+use sha2::{Digest, Sha256};            // Hashing API keys and report bodies for ETags
+use sqlx::{FromRow, Row, SqlitePool};  // Async SQLite DB pool and mapping from query rows
+use std::collections::{BTreeMap, HashMap, HashSet}; // Lookup tables built while aggregating rows
+use std::future::{Ready, ready};       // Synchronous FromRequest implementations
+use std::net::IpAddr;                  // Client addresses used as rate-limit keys
+use std::num::NonZeroUsize;            // LRU cache capacity
+use std::marker::PhantomData;          // Role markers carried by RequireRole
+use std::str::FromStr;                 // Parsing cron expressions
+use std::sync::{Arc, Mutex};           // Shared attendance repository and rate-limit state
+use std::time::Instant;                // Monotonic clock for rate-limit windows
+use tracing::Instrument;               // Running request futures inside their tracing span
+use tracing_subscriber::EnvFilter;     // RUST_LOG-driven log filtering
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme}; // Auth schemes in the OpenAPI document
+use utoipa::{IntoParams, Modify, OpenApi, ToSchema}; // OpenAPI document generation
+use utoipa_swagger_ui::SwaggerUi;      // Swagger UI served at /docs
+use uuid::Uuid;                        // Per-request identifiers
 
 // Attendance represents a single attendance record in the database and in API requests.
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
 struct Attendance {
+    // Primary key assigned by the database; values in request bodies are ignored.
+    #[serde(default, skip_deserializing)]
+    id: Option<i64>,
     student_id: i32,
-    date: String,   // Date in "YYYY-MM-DD" format
-    status: String, // "Present" or "Absent"
+    date: String,                       // Date in "YYYY-MM-DD" format
+    status: AttendanceStatus,           // Stored as "Present", "Absent" or "Late"
+    session: Option<AttendanceSession>, // "AM" or "PM"; omitted means the morning session
+    recorded_by: Option<String>,        // Staff member who entered the record, if known
+    // Facilitator credited with the record, set from the caller's account; values in request
+    // bodies are ignored.
+    #[serde(default, skip_deserializing)]
+    facilitator_id: Option<i64>,
+    notes: Option<String>, // Free-text reason or comment, e.g. why a student was absent
+    // Set by the database; values in request bodies are ignored.
+    #[serde(default, skip_deserializing)]
+    created_at: Option<String>, // When the record was inserted, NULL for rows predating the column
+    #[serde(default, skip_deserializing)]
+    last_updated: Option<String>, // When the record was last inserted or changed
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    deleted_at: Option<String>, // When the record was soft-deleted; None while it is active
+}
+
+// AttendanceStatus is the allowlist of statuses a record may carry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, sqlx::Type, ToSchema)]
+enum AttendanceStatus {
+    Present,
+    Absent,
+    Late,
+}
+
+impl AttendanceStatus {
+    // Canonical capitalized form stored in the database.
+    fn as_str(&self) -> &'static str {
+        match self {
+            AttendanceStatus::Present => "Present",
+            AttendanceStatus::Absent => "Absent",
+            AttendanceStatus::Late => "Late",
+        }
+    }
+}
+
+// Statuses are matched case-insensitively so "present" and "PRESENT" are accepted.
+impl FromStr for AttendanceStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "present" => Ok(AttendanceStatus::Present),
+            "absent" => Ok(AttendanceStatus::Absent),
+            "late" => Ok(AttendanceStatus::Late),
+            _ => Err(format!(
+                "Invalid status '{}', expected Present, Absent or Late",
+                value
+            )),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AttendanceStatus {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value
+            .parse()
+            .map_err(|_| serde::de::Error::unknown_variant(&value, &["Present", "Absent", "Late"]))
+    }
+}
+
+// AttendanceSession is the part of the day a record covers, for programs that meet twice daily.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "UPPERCASE")]
+#[sqlx(rename_all = "UPPERCASE")]
+enum AttendanceSession {
+    #[default]
+    Am,
+    Pm,
+}
+
+impl AttendanceSession {
+    // Canonical uppercase form stored in the database.
+    fn as_str(&self) -> &'static str {
+        match self {
+            AttendanceSession::Am => "AM",
+            AttendanceSession::Pm => "PM",
+        }
+    }
+}
+
+// Sessions are matched case-insensitively so "am" and "Am" are accepted.
+impl FromStr for AttendanceSession {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_uppercase().as_str() {
+            "AM" => Ok(AttendanceSession::Am),
+            "PM" => Ok(AttendanceSession::Pm),
+            _ => Err(format!("Invalid session '{}', expected AM or PM", value)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AttendanceSession {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value
+            .parse()
+            .map_err(|_| serde::de::Error::unknown_variant(&value, &["AM", "PM"]))
+    }
+}
+
+// SessionQuery optionally narrows a per-date endpoint to one session.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SessionQuery {
+    session: Option<AttendanceSession>, // Every session of the date when omitted
+}
+
+// StatusUpdate is the request body for PUT /attendance/{student_id}/{date}.
+#[derive(Debug, Deserialize, ToSchema)]
+struct StatusUpdate {
+    status: AttendanceStatus,
+    notes: Option<String>, // Replaces the stored notes when present; omitted keeps them
 }
 
 // DailyReport represents aggregated attendance counts for a specific date.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 struct DailyReport {
-    date: String,       // Date in "MM-DD-YYYY" format for client readability
+    date: String, // Date in "MM-DD-YYYY" format for client readability
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<String>, // "AM" or "PM" when the totals cover one session of the day
     present_count: i32, // Number of students present
-    absent_count: i32,  // Number of students absent
+    absent_count: i32, // Number of students absent
+    late_count: i32, // Number of students late
+}
+
+// WeeklyReport represents aggregated attendance counts for one ISO calendar week.
+#[derive(Debug, Serialize, ToSchema)]
+struct WeeklyReport {
+    week: String, // ISO week in "YYYY-Www" format, e.g. "2024-W03"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<String>, // "AM" or "PM" when the totals cover one session of the week
+    present_count: i32, // "Present" records across the week
+    absent_count: i32, // "Absent" records across the week
+    total_students: i32, // Distinct students with at least one record that week
+}
+
+// MonthlyReport represents aggregated attendance counts for one calendar month.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct MonthlyReport {
+    month: String, // "YYYY-MM"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<String>, // "AM" or "PM" when the totals cover one session of the month
+    present_count: i32,
+    absent_count: i32,
+    late_count: i32,
+    attendance_rate: f64, // present / total records, 0.0-1.0
+}
+
+// MonthlyReportQuery optionally restricts GET /report/monthly to one calendar year.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct MonthlyReportQuery {
+    year: Option<i32>,
+}
+
+// RecordedDatesQuery optionally restricts GET /attendance/dates to one student.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct RecordedDatesQuery {
+    student_id: Option<i32>,
+}
+
+// GroupFilterQuery holds the optional ?group_id= filter of attendance listings and exports.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GroupFilterQuery {
+    group_id: Option<i32>, // Only records of students in this group
+}
+
+// HeatmapQuery selects the calendar year for GET /report/heatmap.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct HeatmapQuery {
+    year: i32,
+}
+
+// HeatmapCell is one day of a calendar heatmap.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct HeatmapCell {
+    date: String, // "YYYY-MM-DD"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<String>, // "AM" or "PM" when the cell covers one session of the day
+    value: f64,   // present / total records that day, 0.0-1.0
+}
+
+// CompareQuery holds the two inclusive "YYYY-MM-DD" ranges compared by GET /report/compare.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct CompareQuery {
+    period1_start: String,
+    period1_end: String,
+    period2_start: String,
+    period2_end: String,
+}
+
+// PeriodStats aggregates attendance over one date range.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct PeriodStats {
+    total_records: i64,
+    present_count: i64,
+    absent_count: i64,
+    attendance_rate: f64, // present / total records, 0.0 when the period has none
+}
+
+// PeriodComparison is the response of GET /report/compare.
+#[derive(Debug, Serialize, ToSchema)]
+struct PeriodComparison {
+    period1: PeriodStats,
+    period2: PeriodStats,
+    delta_present_rate: f64, // period2 rate minus period1 rate; positive means improvement
+}
+
+// QuarterlyReport represents aggregated attendance counts for one calendar quarter.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct QuarterlyReport {
+    quarter: String, // "YYYY-Qn"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<String>, // "AM" or "PM" when the totals cover one session of the quarter
+    present_count: i32,
+    absent_count: i32,
+    attendance_rate: f64, // present / total records, 0.0-1.0
+}
+
+// YearSummary aggregates every record of one calendar year for GET /report/year/{year}.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct YearSummary {
+    year: i32,
+    present: i64,
+    absent: i64,
+    late: i64,
+    attendance_rate: f64,         // present / total records, 0.0-1.0
+    total_days_with_records: i64, // Distinct dates with at least one record
+    unique_students: i64,
+}
+
+// Years accepted by GET /report/year/{year}.
+const REPORT_YEARS: std::ops::RangeInclusive<i32> = 2000..=2100;
+
+// QuarterlyReportQuery optionally restricts GET /report/quarterly to one calendar year.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct QuarterlyReportQuery {
+    year: Option<i32>,
+}
+
+// Stats is an overall health check of the attendance data.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct Stats {
+    total_records: i64,
+    unique_students: i64,
+    unique_dates: i64,
+    overall_present_rate: f64, // Fraction of all records marked "Present"
+    earliest_date: Option<String>, // None when there are no records
+    latest_date: Option<String>,
+}
+
+// PaginationQuery holds the `?page=`/`?per_page=` parameters of list endpoints.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PaginationQuery {
+    page: Option<u32>,     // 1-based, defaults to 1
+    per_page: Option<u32>, // Defaults to 50, capped at MAX_PER_PAGE
+}
+
+impl PaginationQuery {
+    // Validates the parameters and returns (page, per_page, offset).
+    fn resolve(&self) -> Result<(u32, u32, i64), String> {
+        let page = self.page.unwrap_or(1);
+        let per_page = self.per_page.unwrap_or(DEFAULT_PER_PAGE).min(MAX_PER_PAGE);
+        if page == 0 || per_page == 0 {
+            return Err("page and per_page must be at least 1".to_string());
+        }
+        Ok((page, per_page, (page as i64 - 1) * per_page as i64))
+    }
+}
+
+// PaginatedResponse wraps one page of results with the information needed to fetch the rest.
+#[derive(Debug, Serialize, ToSchema)]
+struct PaginatedResponse<T> {
+    data: Vec<T>,
+    page: u32,
+    per_page: u32,
+    total: i64, // Number of items across all pages
+}
+
+// Page size used when `per_page` is omitted.
+const DEFAULT_PER_PAGE: u32 = 50;
+
+// Largest page size a client may request.
+const MAX_PER_PAGE: u32 = 500;
+
+// SummaryCard is a compact snapshot of recent attendance for mobile home screens.
+#[derive(Debug, Serialize, ToSchema)]
+struct SummaryCard {
+    today_date: String, // Date in "YYYY-MM-DD" format
+    today_present: i32, // "Present" records today, one per student and session
+    today_absent: i32,  // "Absent" records today, one per student and session
+    today_rate: f64,    // Fraction of today's records marked "Present"
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    today_sessions: Vec<SessionSummary>, // Per-session totals when today has AM and PM records
+    week_rate: f64,     // Present rate since Monday of the current week
+    month_rate: f64,    // Present rate since the first of the current month
+    alerts: i32,        // Number of students with 3 or more consecutive absences
+}
+
+// SessionSummary is one session's share of a day's records.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct SessionSummary {
+    session: String, // "AM" or "PM"
+    present: i32,    // "Present" records in the session
+    absent: i32,     // "Absent" records in the session
+    rate: f64,       // Fraction of the session's records marked "Present"
+}
+
+// Student is a row of the `students` table.
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
+struct Student {
+    id: i32,
+    name: String,
+    email: Option<String>,
+    grade: Option<String>,
+    group_id: Option<i32>,
+    address_zip: Option<String>,
+    birth_date: Option<String>,      // "YYYY-MM-DD"
+    referral_source: Option<String>, // How the student heard about the program
+    family_id: Option<i32>,          // Shared by siblings; None when unknown
+}
+
+// StudentDetail is the body of GET /students/{id}: the student row plus figures derived from
+// related tables.
+#[derive(Debug, Serialize, ToSchema)]
+struct StudentDetail {
+    #[serde(flatten)]
+    student: Student,
+    sibling_count: i64, // Other students with the same family_id; 0 when it is unknown
+    intervention_count: i64, // Interventions logged through POST /students/{id}/interventions
+}
+
+// Group is a row of the `groups` table: a cohort students can be assigned to.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct Group {
+    id: i32,
+    name: String,
+    description: Option<String>,
+}
+
+// NewGroup is the request body for POST /groups.
+#[derive(Debug, Deserialize, ToSchema)]
+struct NewGroup {
+    name: String,
+    description: Option<String>,
+}
+
+// GroupReport is the response of GET /groups/{id}/report.
+#[derive(Debug, Serialize, ToSchema)]
+struct GroupReport {
+    group_id: i32,
+    name: String,
+    student_count: i64, // Students currently assigned to the group
+    #[serde(flatten)]
+    stats: PeriodStats, // Attendance of those students over the requested range
+}
+
+// Facilitator is a row of the `facilitators` table: a person attendance records are credited to.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct Facilitator {
+    id: i64,
+    name: String,
+    email: Option<String>,
+    username: Option<String>, // Linked user account; records it enters are credited here
+}
+
+// StudentRecord names one student in a per-day status list such as GET /report/absent/{date}.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct StudentRecord {
+    student_id: i32,
+    name: Option<String>, // None when the student row no longer exists
+}
+
+// NewStudent is the request body for POST /students and PUT /students/{id}.
+#[derive(Debug, Deserialize, ToSchema)]
+struct NewStudent {
+    name: String,
+    email: Option<String>,
+    grade: Option<String>,
+    group_id: Option<i32>,
+    address_zip: Option<String>,
+    birth_date: Option<String>,
+    referral_source: Option<String>,
+    family_id: Option<i32>,
+}
+
+// StudentUpdates lists the student fields a bulk update may change; omitted fields are left as-is.
+#[derive(Debug, Deserialize, ToSchema)]
+struct StudentUpdates {
+    grade: Option<String>, // New grade, e.g. "7"
+    group_id: Option<i32>, // New group assignment
+}
+
+// BulkStudentUpdate is the request body for PATCH /students/bulk.
+#[derive(Debug, Deserialize, ToSchema)]
+struct BulkStudentUpdate {
+    ids: Vec<i32>,           // IDs of the students to update
+    updates: StudentUpdates, // Changes applied to every listed student
+}
+
+// BulkUpdateResult reports how many students a bulk update changed.
+#[derive(Debug, Serialize, ToSchema)]
+struct BulkUpdateResult {
+    updated: u64,
+}
+
+// BulkInsertResult reports how many attendance records POST /attendance/bulk stored.
+#[derive(Debug, Serialize, ToSchema)]
+struct BulkInsertResult {
+    inserted: usize,
+}
+
+// UpsertResult tells the caller of PUT /attendance/upsert whether a record was created or changed.
+#[derive(Debug, Serialize, ToSchema)]
+struct UpsertResult {
+    action: &'static str, // "inserted" or "updated"
+}
+
+// RecordError explains why one entry of a bulk request was rejected.
+#[derive(Debug, Serialize, ToSchema)]
+struct RecordError {
+    index: usize, // Zero-based position of the entry in the request array
+    reason: String,
+}
+
+// BulkValidationFailed is the 422 body returned when any entry of a bulk request is invalid.
+// It extends ApiError with the list of offending entries.
+#[derive(Debug, Serialize, ToSchema)]
+struct BulkValidationFailed {
+    code: u16,
+    message: String,
+    errors: Vec<RecordError>,
+}
+
+// CsvAttendanceRow is one raw row of an uploaded CSV, using the export's column headings.
+#[derive(Debug, Deserialize)]
+struct CsvAttendanceRow {
+    #[serde(rename = "Student ID")]
+    student_id: String,
+    #[serde(rename = "Date")]
+    date: String,
+    #[serde(rename = "Status")]
+    status: String,
+    #[serde(rename = "Notes", default)]
+    notes: Option<String>, // Optional column; blank cells mean no notes
+    #[serde(rename = "Session", default)]
+    session: Option<String>, // Optional column; blank cells mean the morning session
+}
+
+// ImportRowError explains why one row of an uploaded CSV was skipped.
+#[derive(Debug, Serialize, ToSchema)]
+struct ImportRowError {
+    row: u64, // Line number in the uploaded file; the header is line 1
+    reason: String,
+}
+
+// ImportResult summarizes a POST /import/csv upload.
+#[derive(Debug, Serialize, ToSchema)]
+struct ImportResult {
+    inserted: usize,
+    skipped: usize,
+    errors: Vec<ImportRowError>,
+}
+
+// Largest number of records accepted by POST /attendance/bulk in one request.
+const MAX_BULK_RECORDS: usize = 1000;
+
+// Largest request body accepted, in bytes: JSON bodies, raw payloads and CSV uploads alike.
+// A full bulk attendance batch fits comfortably.
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+// StaffRecordingPattern summarizes the attendance records entered by one staff member.
+#[derive(Debug, Serialize, ToSchema)]
+struct StaffRecordingPattern {
+    staff_id: String,         // Value of `recorded_by`
+    dates_recorded: i64,      // Number of distinct dates this staff member recorded
+    avg_present_rate: f64,    // Mean per-day present rate across those dates
+    avg_records_per_day: f64, // Mean number of records entered per date
+    outlier: bool,            // True when avg_present_rate is more than 2σ from the staff mean
+}
+
+// DateRangeQuery holds optional inclusive `from`/`to` bounds ("YYYY-MM-DD") for report queries.
+// `start`/`end` are accepted as aliases.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DateRangeQuery {
+    #[serde(alias = "start")]
+    from: Option<String>,
+    #[serde(alias = "end")]
+    to: Option<String>,
+}
+
+impl DateRangeQuery {
+    // Validates both bounds and returns them as SQL-ready strings, open-ended when omitted.
+    fn bounds(&self) -> Result<(String, String), String> {
+        let parse = |value: &Option<String>, default: &str| match value {
+            Some(v) => NaiveDate::parse_from_str(v, "%Y-%m-%d")
+                .map(|d| d.format("%Y-%m-%d").to_string())
+                .map_err(|_| format!("Invalid date '{}', expected YYYY-MM-DD", v)),
+            None => Ok(default.to_string()),
+        };
+        Ok((
+            parse(&self.from, "0000-01-01")?,
+            parse(&self.to, "9999-12-31")?,
+        ))
+    }
+}
+
+// ExportQuery holds the optional query parameters of GET /export.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ExportQuery {
+    filename: Option<String>, // Overrides the default "attendance_YYYY-MM-DD.csv" download name
+    format: Option<String>,   // "csv" (default) or "tsv"
+    #[serde(flatten)]
+    #[param(ignore)]
+    range: DateRangeQuery, // Optional ?start=/?end= bounds; omit both for a full export
+}
+
+// Longest download filename accepted from ?filename=, in characters.
+const MAX_EXPORT_FILENAME_CHARS: usize = 64;
+
+// TodaySummary is today's attendance at a glance.
+#[derive(Debug, Serialize, ToSchema)]
+struct TodaySummary {
+    date: String,      // Today's date in "YYYY-MM-DD" format
+    present: i32,      // "Present" records today, one per student and session
+    absent: i32,       // "Absent" records today, one per student and session
+    rate: f64,         // Fraction of today's records marked "Present"
+    not_recorded: i32, // Enrolled students with no record for today
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    sessions: Vec<SessionSummary>, // Per-session totals when today has AM and PM records
+}
+
+// StreakBucket counts the students whose current streak has a given length.
+#[derive(Debug, Serialize, ToSchema)]
+struct StreakBucket {
+    streak_length: u32,
+    student_count: i64,
+}
+
+// StreakDistribution summarizes current "Present" streaks across all students.
+#[derive(Debug, Serialize, ToSchema)]
+struct StreakDistribution {
+    distribution: Vec<StreakBucket>, // One bucket per observed streak length, ascending
+    mean_streak: f64,
+    max_streak: u32,
+    median_streak: f64,
+}
+
+// ZipAttendance is the attendance rate for students living in one ZIP code.
+#[derive(Debug, Serialize, ToSchema)]
+struct ZipAttendance {
+    zip_code: Option<String>, // None groups students without an address on file
+    student_count: i64,       // Students with at least one record in this ZIP code
+    present_rate: f64,        // Fraction of this ZIP code's records marked "Present"
+}
+
+// Notification is a single alert that staff should act on today.
+#[derive(Debug, Serialize, ToSchema)]
+struct Notification {
+    #[serde(rename = "type")]
+    kind: String, // "consecutive_absences", "first_absence", "milestone" or "anniversary"
+    student_id: i32,
+    name: String,
+    message: String,
+    priority: String, // "high", "medium" or "low"
+}
+
+// Incentive tiers and the "Present" records needed to reach each, in ascending order.
+// Reaching a tier is also celebrated as a milestone in the daily notifications.
+const INCENTIVE_TIERS: [(&str, i64); 4] = [
+    ("Bronze", 10),
+    ("Silver", 25),
+    ("Gold", 50),
+    ("Platinum", 100),
+];
+
+// Birthday is a student whose birthday falls in the current week.
+#[derive(Debug, Serialize, ToSchema)]
+struct Birthday {
+    student_id: i32,
+    name: String,
+    birth_date: String, // Date in "YYYY-MM-DD" format
+    age_turning: u32,   // Age the student reaches on this week's birthday
+}
+
+// AtRiskQuery holds the parameters of GET /students/at-risk.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AtRiskQuery {
+    threshold: Option<i64>, // Minimum absences to be listed, defaults to DEFAULT_AT_RISK_THRESHOLD
+    since: Option<String>,  // Only count absences on or after this "YYYY-MM-DD" date
+}
+
+// AtRiskStudent is a student whose absences meet the at-risk threshold.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct AtRiskStudent {
+    student_id: i32,
+    name: Option<String>, // None when the student row no longer exists
+    absent_count: i64,
+}
+
+// StudentStats summarizes one student's attendance for GET /students/{id}/stats.
+#[derive(Debug, Serialize, ToSchema)]
+struct StudentStats {
+    student_id: i32,
+    total_days: i64, // Records in the requested range
+    present: i64,
+    absent: i64,
+    late: i64,
+    attendance_rate: f64, // present / total_days, 0.0 when the range holds no records
+}
+
+// StudentStreak is a student's run of consecutive Present sessions for GET /students/{id}/streak.
+#[derive(Debug, Serialize, ToSchema)]
+struct StudentStreak {
+    current_streak: u32, // Run ending at the most recent session
+    longest_streak: u32,
+}
+
+// Absences that put a student on GET /students/at-risk when no ?threshold= is given.
+const DEFAULT_AT_RISK_THRESHOLD: i64 = 3;
+
+// EntryTimeliness measures how long after a session its attendance was entered.
+#[derive(Debug, Serialize, ToSchema)]
+struct EntryTimeliness {
+    date: String,            // Session date in "YYYY-MM-DD" format
+    avg_hours_to_entry: f64, // Mean hours between the session date and record creation
+    late_entries: i64,       // Records created more than 24 hours after the session date
+}
+
+// SurvivalQuery selects the enrollment cohort for GET /report/cohort-survival-curve.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SurvivalQuery {
+    cohort_month: String, // Enrollment month in "YYYY-MM" format
+}
+
+// SurvivalPoint is the share of a cohort still attending some months after enrolling.
+#[derive(Debug, Serialize, ToSchema)]
+struct SurvivalPoint {
+    months_since_enrollment: u32,
+    retention: f64, // Fraction of the cohort with a "Present" record in that month
+}
+
+// ReferralAttendance is the average attendance of students from one referral source.
+#[derive(Debug, Serialize, ToSchema)]
+struct ReferralAttendance {
+    source: Option<String>, // None groups students without a recorded source
+    student_count: i64,     // Students with at least one attendance record
+    avg_present_rate: f64,  // Mean of the individual students' present rates
+}
+
+// ContactInfo holds a student's parent/guardian contact details. It is stored in its own
+// table and served from its own route so it never travels with attendance or student data.
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
+struct ContactInfo {
+    parent_name: Option<String>,
+    phone: Option<String>,
+    email: Option<String>,
+}
+
+// DashboardMetrics gathers the program's key performance indicators in one object.
+#[derive(Debug, Serialize, ToSchema)]
+struct DashboardMetrics {
+    total_enrolled: i64,      // Students in the students table
+    active_last_30_days: i64, // Students present at least once in the last 30 days
+    overall_rate: f64,        // Fraction of all records marked "Present"
+    this_week_rate: f64,      // Present rate since Monday of the current week
+    at_risk_count: i64,       // Students with 3 or more consecutive absences
+    perfect_attendees: i64,   // Students whose every record is "Present"
+    avg_streak: f64,          // Mean current "Present" streak across students
+    data_completeness: f64,   // Share of expected (enrolled student, program day) records present
+}
+
+// Intervention is an outreach action a case manager logged against a student.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct Intervention {
+    id: i64,
+    student_id: i32,
+    intervention_type: String, // e.g. "phone_call", "home_visit"
+    notes: Option<String>,
+    outcome: Option<String>,
+    staff_id: Option<String>, // Staff member who performed the intervention
+    created_at: String,       // Timestamp set by the database
+}
+
+// NewIntervention is the request body for POST /students/{id}/interventions.
+#[derive(Debug, Deserialize, ToSchema)]
+struct NewIntervention {
+    intervention_type: String,
+    notes: Option<String>,
+    outcome: Option<String>,
+    staff_id: Option<String>,
+}
+
+// AuditTrailQuery identifies the attendance record whose history is requested.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AuditTrailQuery {
+    student_id: i32,
+    date: String, // Date in "YYYY-MM-DD" format
+}
+
+// AuditEntry is one recorded change to an attendance record.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct AuditEntry {
+    changed_at: String,
+    changed_by: String,         // "unknown" when the change was not attributed
+    old_status: Option<String>, // None for inserts
+    new_status: Option<String>, // None for deletes
+    operation: String,          // "INSERT", "UPDATE" or "DELETE"
+}
+
+// AuditLogQuery holds the filters of GET /admin/audit-log; omitted filters match everything.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AuditLogQuery {
+    table_name: Option<String>, // e.g. "attendance" or "students"
+    action: Option<String>,     // "INSERT", "UPDATE" or "DELETE", case-insensitive
+    #[serde(flatten)]
+    #[param(ignore)]
+    range: DateRangeQuery, // Inclusive bounds on the day of the change (UTC)
+}
+
+// AuditLogEntry is one audit_log row as returned by GET /admin/audit-log.
+#[derive(Debug, Serialize, ToSchema)]
+struct AuditLogEntry {
+    id: i64,
+    table_name: String,
+    record_id: i64,
+    action: String,
+    old_value: Option<serde_json::Value>, // Snapshot before the change; None for inserts
+    new_value: Option<serde_json::Value>, // Snapshot after the change; None for deletes
+    performed_by: Option<String>,
+    performed_at: String,
+}
+
+// Actions an audit_log row may record, matching its CHECK constraint.
+const AUDIT_ACTIONS: [&str; 3] = ["INSERT", "UPDATE", "DELETE"];
+
+// RecoveryQuery sets the window for GET /report/attendance-recovery.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct RecoveryQuery {
+    look_back_weeks: Option<u32>, // Defaults to 6 weeks
+}
+
+// RecoveryStory is a student whose attendance improved across the look-back window.
+#[derive(Debug, Serialize, ToSchema)]
+struct RecoveryStory {
+    student_id: i32,
+    name: Option<String>,
+    earlier_rate: f64, // Present rate in the first half of the window
+    recent_rate: f64,  // Present rate in the second half of the window
+    improvement: f64,  // recent_rate - earlier_rate
+}
+
+// Minimum rise in present rate for a student to count as recovering.
+const RECOVERY_THRESHOLD: f64 = 0.15;
+
+// GroupComparisonQuery filters GET /report/group-comparison-over-time.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GroupComparisonQuery {
+    #[serde(flatten)]
+    #[param(ignore)]
+    range: DateRangeQuery,
+    group_ids: Option<String>, // Comma-separated group IDs, e.g. "1,3"; all groups when omitted
+}
+
+// GroupRate is one group's present rate within a week.
+#[derive(Debug, Serialize, ToSchema)]
+struct GroupRate {
+    group_id: i32,
+    group_name: String,
+    rate: f64,
+}
+
+// GroupWeek holds every group's rate for one ISO week.
+#[derive(Debug, Serialize, ToSchema)]
+struct GroupWeek {
+    week: String, // ISO week label, e.g. "2024-W03"
+    groups: Vec<GroupRate>,
+}
+
+// ScheduledExport is a recurring export job stored in the scheduled_exports table.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct ScheduledExport {
+    id: i64,
+    cron: String,                // Cron expression evaluated in the server's local time
+    format: String,              // Export format; currently only "csv"
+    recipient: String,           // Email address the export is sent to
+    last_run_at: Option<String>, // UTC timestamp of the last run, if any
+    created_at: String,
+}
+
+// NewScheduledExport is the request body for POST /report/export-scheduled.
+#[derive(Debug, Deserialize, ToSchema)]
+struct NewScheduledExport {
+    cron: String,
+    format: String,
+    recipient: String,
+}
+
+// How often the scheduler wakes up to look for due exports.
+const EXPORT_SCHEDULER_TICK: std::time::Duration = std::time::Duration::from_secs(60);
+
+// Body of a successful POST /attendance response.
+const ATTENDANCE_RECORDED: &str = "Attendance recorded";
+
+// Optional header on POST /attendance naming a retry-safe submission (a UUID).
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+// How long a stored idempotency key is replayed before the cleanup task deletes it.
+const IDEMPOTENCY_KEY_TTL_HOURS: i64 = 24;
+
+// How often the cleanup task looks for expired idempotency keys.
+const IDEMPOTENCY_CLEANUP_TICK: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+// TrajectoryPoint is a present rate within one two-week window after enrollment.
+#[derive(Debug, Serialize, ToSchema)]
+struct TrajectoryPoint {
+    window: u32, // 0 is the first two weeks after enrollment
+    rate: f64,
+}
+
+// StudentTrajectory is one student's present rate over their enrollment lifetime.
+#[derive(Debug, Serialize, ToSchema)]
+struct StudentTrajectory {
+    student_id: i32,
+    trajectory: Vec<TrajectoryPoint>, // Windows without records are omitted
+}
+
+// ImprovementTrajectories pairs every student's trajectory with the program-wide average.
+#[derive(Debug, Serialize, ToSchema)]
+struct ImprovementTrajectories {
+    students: Vec<StudentTrajectory>,
+    average: Vec<TrajectoryPoint>, // Mean of the student rates in each window
+}
+
+// (present, total) record counts accumulated while aggregating in Rust.
+type PresentCounts = (i64, i64);
+
+// Length in days of one trajectory window.
+const TRAJECTORY_WINDOW_DAYS: i64 = 14;
+
+// DataEntryStreak measures how consistently attendance has been entered day after day.
+#[derive(Debug, Serialize, ToSchema)]
+struct DataEntryStreak {
+    current_streak_days: u32, // Consecutive program days with at least one record created
+    last_entry_date: Option<String>, // Most recent day a record was created
+    first_gap_date: Option<String>, // Most recent program day with no records created
+}
+
+// ParityQuery picks the student attribute GET /report/attendance-parity groups by.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ParityQuery {
+    group_by: String, // "grade", "group_id", "referral_source" or "address_zip"
+}
+
+// ParitySubgroup is the attendance rate of one subgroup of students.
+#[derive(Debug, Serialize, ToSchema)]
+struct ParitySubgroup {
+    subgroup: String, // "unknown" for students without a value
+    rate: f64,        // Fraction of the subgroup's records marked "Present"
+    student_count: i64,
+}
+
+// ParityReport compares subgroups and summarizes the spread in a single gap figure.
+#[derive(Debug, Serialize, ToSchema)]
+struct ParityReport {
+    group_by: String,
+    subgroups: Vec<ParitySubgroup>,
+    max_rate_gap: f64, // Highest subgroup rate minus the lowest
+}
+
+// StaffDayPattern counts the records one staff member entered for sessions on one weekday.
+#[derive(Debug, Serialize, ToSchema)]
+struct StaffDayPattern {
+    staff_id: String,    // Value of `recorded_by`
+    day_of_week: String, // e.g. "Monday"
+    records: i64,
+}
+
+// Weekday names indexed by SQLite's strftime('%w') value (0 = Sunday).
+const WEEKDAY_NAMES: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+// ReenrollmentEvent is a student returning after dropping out.
+#[derive(Debug, Serialize, ToSchema)]
+struct ReenrollmentEvent {
+    student_id: i32,
+    name: Option<String>,
+    gap_start: String, // Last "Present" date before the gap
+    gap_end: String,   // First "Present" date after the gap
+    gap_days: i64,     // Days between gap_start and gap_end
+}
+
+// Days without a "Present" record after which a student is considered to have dropped out.
+const DROPOUT_GAP_DAYS: i64 = 30;
+
+// PeerPair is two students who tend to be absent on the same days.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct PeerPair {
+    student_a: i32,
+    student_b: i32,
+    co_absence_rate: f64, // Days both absent / days at least one absent (Jaccard similarity)
+    common_absences: i64, // Days both were absent
+}
+
+// WeekdayRate is the average daily present rate of sessions held on one weekday.
+#[derive(Debug, Serialize, ToSchema)]
+struct WeekdayRate {
+    weekday: String,  // e.g. "Tuesday"
+    avg_rate: f64,    // Mean of the per-date present rates
+    sample_size: i64, // Number of session dates on this weekday
+}
+
+// OptimalSessionDay ranks weekdays by attendance and names the best one.
+#[derive(Debug, Serialize, ToSchema)]
+struct OptimalSessionDay {
+    recommended_day: Option<String>, // None when there is no attendance data yet
+    weekdays: Vec<WeekdayRate>,      // Sorted by avg_rate, highest first
+}
+
+// IncentiveEligibility shows where a student stands in the incentive tiers.
+#[derive(Debug, Serialize, ToSchema)]
+struct IncentiveEligibility {
+    current_tier: Option<String>,   // Highest tier reached, if any
+    sessions_toward_next_tier: i32, // "Present" records still needed for the next tier
+    next_tier: Option<String>,      // None once the top tier is reached
+    total_present: i32,
+}
+
+// MonthlyGridQuery selects the calendar year for GET /report/all-students-monthly-grid.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct MonthlyGridQuery {
+    year: i32,
+}
+
+// MonthlyGridRow is one student's row of monthly present rates.
+#[derive(Debug, Serialize, ToSchema)]
+struct MonthlyGridRow {
+    student_id: i32,
+    name: String,
+    monthly_rates: Vec<Option<f64>>, // One entry per month; None when there are no records
+}
+
+// MonthlyGrid is a students x months table ready for spreadsheet export.
+#[derive(Debug, Serialize, ToSchema)]
+struct MonthlyGrid {
+    months: Vec<String>, // Column labels in "YYYY-MM" format
+    students: Vec<MonthlyGridRow>,
+}
+
+// GradeTrend is the direction of one grade's weekly attendance rate.
+#[derive(Debug, Serialize, ToSchema)]
+struct GradeTrend {
+    grade: String,          // "unknown" for students without a grade
+    slope: f64,             // Change in weekly present rate per week
+    r_squared: f64,         // Goodness of fit of the regression line
+    interpretation: String, // "improving", "stable" or "declining"
+}
+
+// Number of weeks covered by GET /report/attendance-trend-by-grade.
+const TREND_WEEKS: i64 = 12;
+
+// Minimum weekly change in rate (one percentage point) for a trend to count as moving.
+const TREND_SLOPE_THRESHOLD: f64 = 0.01;
+
+// Minimum fit required before a slope is treated as a real trend rather than noise.
+const TREND_MIN_R_SQUARED: f64 = 0.5;
+
+// BenefitsRiskQuery configures GET /report/students-at-risk-of-losing-benefits.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct BenefitsRiskQuery {
+    threshold: Option<f64>,           // Required attendance rate, defaults to 0.8
+    look_ahead_sessions: Option<i32>, // Sessions available to recover, defaults to 3
+}
+
+// BenefitsRisk is a student whose attendance rate is below the benefits threshold.
+#[derive(Debug, Serialize, ToSchema)]
+struct BenefitsRisk {
+    student_id: i32,
+    name: Option<String>,
+    rate: f64,                               // Current fraction of records marked "Present"
+    sessions_needed_to_recover: Option<i32>, // None when recovery is not possible within the look-ahead
+}
+
+// TwoWeekAlert is a student with no "Present" record in the past two weeks.
+#[derive(Debug, Serialize, ToSchema)]
+struct TwoWeekAlert {
+    student_id: i32,
+    name: Option<String>,
+    last_present: Option<String>, // Most recent "Present" date, if any
+    reason: String, // "absent" when marked absent, "no_record" when nothing was entered
+}
+
+// Calendar days without a "Present" record before a two-week alert is raised.
+const TWO_WEEK_ALERT_DAYS: i64 = 14;
+
+// HealthStatus is the body of GET /health and GET /ready.
+#[derive(Debug, Serialize, ToSchema)]
+struct HealthStatus {
+    status: &'static str, // "ok" or "degraded"
+    db: &'static str,     // "ok" or "error"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>, // Why the check failed
+}
+
+// Longest a health or readiness probe waits on the database before reporting it as failed.
+const HEALTH_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+// API versions and the migration path.
+//
+// Every endpoint lives under /v1, e.g. /v1/attendance, /v1/report and /v1/export. The original
+// unversioned paths (/attendance, /report, ...) still answer with the same handlers so existing
+// integrations keep working. Their responses carry `Deprecated: true` and they will be removed
+// in a future release. To migrate, prefix every request path with /v1; request and response
+// bodies are unchanged. GET /, /health, /ready, /metrics, /openapi.json and /docs are not
+// versioned and stay where they are.
+//
+// A breaking change goes into a new version rather than into v1. Add its entry here, mount a
+// second scope next to /v1 in main, and once it is stable mark v1 deprecated: wrap the /v1 scope
+// in the same Deprecated header and give clients a release or two before removing it.
+
+// ApiVersion is one entry in the GET / document.
+#[derive(Debug, Serialize, ToSchema)]
+struct ApiVersion {
+    version: &'static str,   // e.g. "v1"
+    base_path: &'static str, // Prefix of every endpoint in this version
+    deprecated: bool,        // Responses carry `Deprecated: true`; the version will be removed
+}
+
+// Versions the server answers, newest first.
+const API_VERSIONS: [ApiVersion; 2] = [
+    ApiVersion {
+        version: "v1",
+        base_path: "/v1",
+        deprecated: false,
+    },
+    ApiVersion {
+        version: "unversioned",
+        base_path: "/",
+        deprecated: true,
+    },
+];
+
+// Response header marking an endpoint slated for removal.
+const DEPRECATED_HEADER: &str = "Deprecated";
+
+// ApiIndex is the body of GET /: the available API versions and where the documentation lives.
+#[derive(Debug, Serialize, ToSchema)]
+struct ApiIndex {
+    name: &'static str,
+    versions: &'static [ApiVersion],
+    docs: &'static str,    // Swagger UI
+    openapi: &'static str, // OpenAPI document
+}
+
+// Claims is the payload of the JWTs issued by POST /auth/token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Claims {
+    sub: String, // Username
+    exp: usize,  // Expiry as a Unix timestamp
+    role: Role,
+}
+
+// LoginRequest is the request body for POST /auth/token.
+#[derive(Debug, Deserialize, ToSchema)]
+struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+// TokenResponse carries a freshly issued bearer token.
+#[derive(Debug, Serialize, ToSchema)]
+struct TokenResponse {
+    access_token: String,
+    token_type: String, // Always "Bearer"
+    expires_in: i64,    // Seconds until the token expires
+}
+
+// AuthConfig holds the JWT signing secret and the access policy enforced by `require_auth`.
+#[derive(Debug, Clone)]
+struct AuthConfig {
+    jwt_secret: String,
+    protect_reads: bool, // When true, GET requests need a token as well as writes
+}
+
+impl AuthConfig {
+    // Reads JWT_SECRET and AUTH_PROTECT_READS from the environment. Without JWT_SECRET a random
+    // secret is generated, so issued tokens stop working when the server restarts.
+    fn from_env() -> Self {
+        let jwt_secret = std::env::var("JWT_SECRET")
+            .unwrap_or_else(|_| SaltString::generate(&mut OsRng).to_string());
+        let protect_reads = std::env::var("AUTH_PROTECT_READS")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        AuthConfig {
+            jwt_secret,
+            protect_reads,
+        }
+    }
+}
+
+// SmtpSecurity is how the connection to SMTP_HOST is protected (SMTP_TLS).
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SmtpSecurity {
+    StartTls, // "starttls" (default): upgrade a plain connection, usually on port 587
+    Tls,      // "tls": TLS from the first byte, usually on port 465
+    Plain,    // "none": unencrypted, only for a relay on the same host or network
+}
+
+impl FromStr for SmtpSecurity {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "starttls" => Ok(SmtpSecurity::StartTls),
+            "tls" => Ok(SmtpSecurity::Tls),
+            "none" => Ok(SmtpSecurity::Plain),
+            _ => Err(()),
+        }
+    }
+}
+
+// MailConfig is the SMTP relay scheduled exports are emailed through.
+#[derive(Debug, Clone)]
+struct MailConfig {
+    host: String,                          // SMTP_HOST
+    port: u16,                             // SMTP_PORT, default 587
+    security: SmtpSecurity,                // SMTP_TLS, default "starttls"
+    credentials: Option<(String, String)>, // SMTP_USERNAME/SMTP_PASSWORD if both set
+    from: Mailbox,                         // SMTP_FROM, default "YouthSync <youthsync@localhost>"
+}
+
+impl MailConfig {
+    // Builds the SMTP transport. Nothing is sent until the first export is due.
+    fn transport(
+        &self,
+    ) -> Result<AsyncSmtpTransport<Tokio1Executor>, lettre::transport::smtp::Error> {
+        let builder = match self.security {
+            SmtpSecurity::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)?
+            }
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&self.host)?,
+            SmtpSecurity::Plain => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.host)
+            }
+        };
+        let builder = builder.port(self.port);
+        Ok(match &self.credentials {
+            Some((username, password)) => builder
+                .credentials(Credentials::new(username.clone(), password.clone()))
+                .build(),
+            None => builder.build(),
+        })
+    }
+}
+
+// Mailer sends scheduled exports: the SMTP transport and the From address.
+struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+// Lifetime of an issued token in seconds (1 hour); clients sign in again through POST /auth/token.
+const TOKEN_TTL_SECS: i64 = 60 * 60;
+
+// AuthMethod records which credential an authenticated request presented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AuthMethod {
+    Jwt,    // Authorization: Bearer <token>
+    ApiKey, // X-Api-Key: <key>
+}
+
+// Role is what an authenticated caller may do. Variants are ordered by privilege, so a caller
+// satisfies any requirement at or below its own role.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum Role {
+    Viewer, // Read-only access
+    Staff,  // Records and corrects attendance; API keys act as staff
+    Admin,  // Everything, including deletes and account management
+}
+
+impl Role {
+    // Lowercase form stored in users.role and carried in JWT claims.
+    fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Staff => "staff",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "viewer" => Ok(Role::Viewer),
+            "staff" => Ok(Role::Staff),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!(
+                "Invalid role '{}', expected admin, staff or viewer",
+                value
+            )),
+        }
+    }
+}
+
+// AuthGuard is the authenticated caller. `require_auth` stores one in the request extensions for
+// either a valid JWT or a valid API key; handlers extract it without caring which was used.
+#[derive(Debug, Clone)]
+struct AuthGuard {
+    subject: String, // Username for JWTs, "api-key:<label>" for API keys
+    role: Role,      // API keys act as staff
+    method: AuthMethod,
+}
+
+impl FromRequest for AuthGuard {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<AuthGuard>()
+                .cloned()
+                .ok_or_else(|| AppError::Unauthorized("Authentication required".to_string())),
+        )
+    }
+}
+
+// MinimumRole names the least privileged role a RequireRole extractor accepts. Roles cannot be
+// const generic parameters, so each requirement is a marker type.
+trait MinimumRole {
+    const ROLE: Role;
+}
+
+// Marker for handlers only admins may call.
+struct AdminOnly;
+
+impl MinimumRole for AdminOnly {
+    const ROLE: Role = Role::Admin;
+}
+
+// RequireRole extracts the AuthGuard like AuthGuard itself does, and additionally answers 403 when
+// the caller's role is below R::ROLE. Handlers take it as `RequireRole<AdminOnly>`.
+struct RequireRole<R: MinimumRole>(AuthGuard, PhantomData<R>);
+
+impl<R: MinimumRole> FromRequest for RequireRole<R> {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let guard = match req.extensions().get::<AuthGuard>().cloned() {
+            Some(guard) => guard,
+            None => {
+                return ready(Err(AppError::Unauthorized(
+                    "Authentication required".to_string(),
+                )));
+            }
+        };
+        if guard.role < R::ROLE {
+            return ready(Err(AppError::Forbidden(format!(
+                "'{}' has role {}, this action requires {}",
+                guard.subject,
+                guard.role.as_str(),
+                R::ROLE.as_str()
+            ))));
+        }
+        ready(Ok(RequireRole(guard, PhantomData)))
+    }
+}
+
+// RateLimitState tracks one client's writes within the current fixed window.
+#[derive(Debug)]
+struct RateLimitState {
+    window_start: Instant,
+    count: u32, // Writes seen since window_start
+}
+
+// RateLimiter caps write requests per client IP. It is created once in main and shared by every
+// worker through web::Data.
+#[derive(Debug)]
+struct RateLimiter {
+    max_requests: u32,
+    window: std::time::Duration,
+    clients: Mutex<HashMap<IpAddr, RateLimitState>>,
+}
+
+// Defaults when RATE_LIMIT_REQUESTS / RATE_LIMIT_WINDOW_SECS are unset: 60 writes per minute.
+const DEFAULT_RATE_LIMIT_REQUESTS: u32 = 60;
+const DEFAULT_RATE_LIMIT_WINDOW_SECS: u64 = 60;
+
+// ReportCache keeps recently served GET /report bodies so dashboards that poll the report do not
+// re-aggregate attendance on every refresh. Entries expire after `ttl`, and the whole cache is
+// cleared whenever attendance changes. It is created once in main and shared by every worker
+// through web::Data.
+struct ReportCache {
+    ttl: std::time::Duration,
+    entries: Mutex<LruCache<String, (Instant, String)>>, // Key -> (stored at, JSON body)
+}
+
+// Number of distinct GET /report queries kept in the cache.
+const REPORT_CACHE_CAPACITY: NonZeroUsize = NonZeroUsize::new(256).unwrap();
+
+// How long a cached GET /report body is served before it is recomputed.
+const REPORT_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+
+// Response header saying whether GET /report was served from the cache ("HIT") or not ("MISS").
+const CACHE_STATUS_HEADER: &str = "X-Cache";
+
+// Metrics holds the Prometheus registry and metric handles behind GET /metrics. It is created
+// once in main and shared by every worker through web::Data.
+struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec, // youthsync_requests_total{endpoint, method, status}
+    request_duration: HistogramVec, // youthsync_request_duration_seconds{endpoint}
+    pool_idle_connections: IntGauge, // Refreshed from the pool on every scrape
+    attendance_inserts: IntCounter, // Attendance records stored
+    attendance_insert_errors: IntCounter, // Attendance records rejected or failed to store
+}
+
+// Config groups every setting read from the environment. main builds it once at startup and
+// shares it with handlers through web::Data.
+struct Config {
+    // Server and database
+    host: String,              // HOST, default "127.0.0.1"
+    port: u16,                 // PORT, default 8080
+    database_url: String,      // DATABASE_URL: SQLite file path or "sqlite:" URL
+    pool_min_connections: u32, // POOL_MIN_CONNECTIONS, default 1
+    pool_max_connections: u32, // POOL_MAX_CONNECTIONS, default 5
+    wal_mode: bool,            // SQLITE_WAL, default true; write-ahead logging journal
+    busy_timeout_ms: u32,      // SQLITE_BUSY_TIMEOUT_MS, how long a locked write waits
+
+    // Logging: LOG_LEVEL applies when RUST_LOG is unset; LOG_FORMAT=json selects JSON lines;
+    // requests slower than SLOW_REQUEST_THRESHOLD_MS (default 500) are logged as warnings
+    log_level: String,
+    log_json: bool,
+    slow_request_threshold_ms: u64,
+
+    // CORS: comma-separated CORS_ALLOWED_ORIGINS ("*" = permissive), CORS_ALLOWED_METHODS,
+    // CORS_ALLOWED_HEADERS, and CORS_MAX_AGE_SECS for preflight caching
+    cors_allowed_origins: Vec<String>,
+    cors_allowed_methods: Vec<Method>,
+    cors_allowed_headers: Vec<HeaderName>,
+    cors_max_age_secs: usize,
+
+    // Features
+    export_dir: String, // EXPORT_DIR for scheduled exports, default "./exports"
+    mail: Option<MailConfig>, // SMTP relay for scheduled exports; None when SMTP_HOST is unset
+    program_name: String, // YOUTHSYNC_PROGRAM_NAME shown in calendar exports
+    auth: AuthConfig,   // JWT_SECRET and AUTH_PROTECT_READS
+    rate_limit_requests: u32,
+    rate_limit_window_secs: u64,
+    compression_enabled: bool, // COMPRESSION_ENABLED, default true; gzip/brotli/zstd responses
+    admin_credentials: Option<(String, String)>, // ADMIN_USERNAME/ADMIN_PASSWORD if both set
+}
+
+// Requests taking longer than this are logged as slow when SLOW_REQUEST_THRESHOLD_MS is unset.
+const DEFAULT_SLOW_REQUEST_THRESHOLD_MS: u64 = 500;
+
+// Longest a handler waits for a free pool connection before answering 503.
+const POOL_ACQUIRE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+// CORS defaults used when CORS_ALLOWED_ORIGINS names specific origins.
+const DEFAULT_CORS_METHODS: &str = "GET,POST,PUT,PATCH,DELETE,OPTIONS";
+const DEFAULT_CORS_HEADERS: &str = "Authorization,Content-Type,X-Api-Key";
+const DEFAULT_CORS_MAX_AGE_SECS: usize = 3600;
+
+// Splits a comma-separated environment variable into trimmed, non-empty entries.
+fn env_list(name: &str, default: &str) -> Vec<String> {
+    std::env::var(name)
+        .unwrap_or_else(|_| default.to_string())
+        .split(',')
+        .map(|entry| entry.trim().to_string())
+        .filter(|entry| !entry.is_empty())
+        .collect()
+}
+
+// Parses environment variable `name`, returning `default` when it is unset. A value that does not
+// parse (or fails `valid`) is recorded in `errors` as "<name> must be <expected>".
+fn env_parse<T: FromStr>(
+    name: &str,
+    default: T,
+    expected: &str,
+    valid: impl Fn(&T) -> bool,
+    errors: &mut Vec<String>,
+) -> T {
+    let Ok(raw) = std::env::var(name) else {
+        return default;
+    };
+    match raw.trim().parse::<T>() {
+        Ok(value) if valid(&value) => value,
+        _ => {
+            errors.push(format!("{} must be {}, got '{}'", name, expected, raw));
+            default
+        }
+    }
+}
+
+impl Config {
+    // Reads every setting, applying defaults for unset variables. Fails with one message listing
+    // every variable that is set to an invalid value.
+    fn from_env() -> Result<Self, String> {
+        let var_or =
+            |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+        let mut errors = Vec::new();
+
+        let port = env_parse(
+            "PORT",
+            8080u16,
+            "a port number (0-65535)",
+            |_| true,
+            &mut errors,
+        );
+        let pool_min_connections = env_parse(
+            "POOL_MIN_CONNECTIONS",
+            1u32,
+            "a non-negative integer",
+            |_| true,
+            &mut errors,
+        );
+        let pool_max_connections = env_parse(
+            "POOL_MAX_CONNECTIONS",
+            5u32,
+            "a positive integer",
+            |&n| n > 0,
+            &mut errors,
+        );
+        if pool_min_connections > pool_max_connections {
+            errors.push(format!(
+                "POOL_MIN_CONNECTIONS ({}) must not exceed POOL_MAX_CONNECTIONS ({})",
+                pool_min_connections, pool_max_connections
+            ));
+        }
+        let wal_mode = env_parse("SQLITE_WAL", true, "true or false", |_| true, &mut errors);
+        let busy_timeout_ms = env_parse(
+            "SQLITE_BUSY_TIMEOUT_MS",
+            5000u32,
+            "a number of milliseconds",
+            |_| true,
+            &mut errors,
+        );
+        let rate_limit_requests = env_parse(
+            "RATE_LIMIT_REQUESTS",
+            DEFAULT_RATE_LIMIT_REQUESTS,
+            "a positive integer",
+            |&n| n > 0,
+            &mut errors,
+        );
+        let rate_limit_window_secs = env_parse(
+            "RATE_LIMIT_WINDOW_SECS",
+            DEFAULT_RATE_LIMIT_WINDOW_SECS,
+            "a positive integer",
+            |&n| n > 0,
+            &mut errors,
+        );
+        let slow_request_threshold_ms = env_parse(
+            "SLOW_REQUEST_THRESHOLD_MS",
+            DEFAULT_SLOW_REQUEST_THRESHOLD_MS,
+            "a number of milliseconds",
+            |_| true,
+            &mut errors,
+        );
+        let compression_enabled = env_parse(
+            "COMPRESSION_ENABLED",
+            true,
+            "true or false",
+            |_| true,
+            &mut errors,
+        );
+
+        let database_url = var_or("DATABASE_URL", "./youthsync.db");
+        if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+            errors.push(
+                "DATABASE_URL names PostgreSQL, which is not supported; use SQLite".to_string(),
+            );
+        }
+        if database_url.starts_with("sqlite:")
+            && let Err(e) = sqlx::sqlite::SqliteConnectOptions::from_str(&database_url)
+        {
+            errors.push(format!("DATABASE_URL is not a valid SQLite URL: {}", e));
+        }
+
+        let log_level = var_or("LOG_LEVEL", "info");
+        if EnvFilter::try_new(&log_level).is_err() {
+            errors.push(format!(
+                "LOG_LEVEL must be a level or filter such as \"info\", got '{}'",
+                log_level
+            ));
+        }
+
+        let cors_allowed_origins = env_list("CORS_ALLOWED_ORIGINS", "*");
+        for origin in cors_allowed_origins.iter().filter(|origin| *origin != "*") {
+            let valid = origin
+                .parse::<actix_web::http::Uri>()
+                .is_ok_and(|uri| uri.scheme().is_some() && uri.host().is_some());
+            if !valid {
+                errors.push(format!(
+                    "CORS_ALLOWED_ORIGINS entries must look like https://example.org, got '{}'",
+                    origin
+                ));
+            }
+        }
+        if cors_allowed_origins.is_empty() {
+            errors.push("CORS_ALLOWED_ORIGINS must list at least one origin or \"*\"".to_string());
+        }
+
+        let mut cors_allowed_methods = Vec::new();
+        for method in env_list("CORS_ALLOWED_METHODS", DEFAULT_CORS_METHODS) {
+            match Method::from_str(&method.to_ascii_uppercase()) {
+                Ok(method) => cors_allowed_methods.push(method),
+                Err(_) => errors.push(format!(
+                    "CORS_ALLOWED_METHODS contains an invalid method '{}'",
+                    method
+                )),
+            }
+        }
+        let mut cors_allowed_headers = Vec::new();
+        for name in env_list("CORS_ALLOWED_HEADERS", DEFAULT_CORS_HEADERS) {
+            match HeaderName::from_str(&name) {
+                Ok(name) => cors_allowed_headers.push(name),
+                Err(_) => errors.push(format!(
+                    "CORS_ALLOWED_HEADERS contains an invalid header name '{}'",
+                    name
+                )),
+            }
+        }
+        let cors_max_age_secs = env_parse(
+            "CORS_MAX_AGE_SECS",
+            DEFAULT_CORS_MAX_AGE_SECS,
+            "a number of seconds",
+            |_| true,
+            &mut errors,
+        );
+
+        if !errors.is_empty() {
+            return Err(format!("Invalid configuration: {}", errors.join("; ")));
+        }
+
+        // Scheduled exports are emailed only when SMTP_HOST is set.
+        let mail = match std::env::var("SMTP_HOST") {
+            Ok(host) if !host.trim().is_empty() => {
+                let port = env_parse("SMTP_PORT", 587, "a port number", |_| true, &mut errors);
+                let security = env_parse(
+                    "SMTP_TLS",
+                    SmtpSecurity::StartTls,
+                    "starttls, tls or none",
+                    |_| true,
+                    &mut errors,
+                );
+                let from = var_or("SMTP_FROM", "YouthSync <youthsync@localhost>");
+                let from = from.parse::<Mailbox>().map_err(|_| {
+                    errors.push(format!(
+                        "SMTP_FROM must be an email address, got '{}'",
+                        from
+                    ))
+                });
+                let credentials = match (
+                    std::env::var("SMTP_USERNAME"),
+                    std::env::var("SMTP_PASSWORD"),
+                ) {
+                    (Ok(username), Ok(password)) => Some((username, password)),
+                    _ => None,
+                };
+                from.ok().map(|from| MailConfig {
+                    host: host.trim().to_string(),
+                    port,
+                    security,
+                    credentials,
+                    from,
+                })
+            }
+            _ => None,
+        };
+
+        let admin_credentials = match (
+            std::env::var("ADMIN_USERNAME"),
+            std::env::var("ADMIN_PASSWORD"),
+        ) {
+            (Ok(username), Ok(password)) => Some((username, password)),
+            _ => None,
+        };
+        Ok(Config {
+            host: var_or("HOST", "127.0.0.1"),
+            port,
+            database_url,
+            pool_min_connections,
+            pool_max_connections,
+            wal_mode,
+            busy_timeout_ms,
+            log_level,
+            log_json: var_or("LOG_FORMAT", "").eq_ignore_ascii_case("json"),
+            slow_request_threshold_ms,
+            cors_allowed_origins,
+            cors_allowed_methods,
+            cors_allowed_headers,
+            cors_max_age_secs,
+            export_dir: var_or("EXPORT_DIR", "./exports"),
+            mail,
+            // Quotes would break the iCalendar CN parameter.
+            program_name: var_or("YOUTHSYNC_PROGRAM_NAME", "YouthSync").replace('"', ""),
+            auth: AuthConfig::from_env(),
+            rate_limit_requests,
+            rate_limit_window_secs,
+            compression_enabled,
+            admin_credentials,
+        })
+    }
+
+    // CORS policy for one worker. "*" keeps the permissive policy; otherwise only the listed
+    // origins may call the API, with the configured methods, headers and preflight max-age.
+    fn cors(&self) -> Cors {
+        if self.cors_allowed_origins.iter().any(|origin| origin == "*") {
+            return Cors::permissive();
+        }
+        let cors = self
+            .cors_allowed_origins
+            .iter()
+            .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin));
+        cors.allowed_methods(self.cors_allowed_methods.clone())
+            .allowed_headers(self.cors_allowed_headers.clone())
+            .expose_headers([
+                header::CONTENT_DISPOSITION,
+                header::RETRY_AFTER,
+                HeaderName::from_static("x-request-id"),
+            ])
+            .max_age(self.cors_max_age_secs)
+    }
+
+    // Connection options for DATABASE_URL; the database file is created if missing.
+    fn connect_options(&self) -> Result<sqlx::sqlite::SqliteConnectOptions, sqlx::Error> {
+        let options = if self.database_url.starts_with("sqlite:") {
+            sqlx::sqlite::SqliteConnectOptions::from_str(&self.database_url)?
+        } else {
+            sqlx::sqlite::SqliteConnectOptions::new().filename(&self.database_url)
+        };
+        Ok(options.create_if_missing(true))
+    }
+}
+
+// Number of tracked clients above which expired windows are pruned.
+const RATE_LIMIT_PRUNE_THRESHOLD: usize = 10_000;
+
+impl RateLimiter {
+    // Allows `max_requests` writes per client in every `window_secs` window.
+    fn new(max_requests: u32, window_secs: u64) -> Self {
+        RateLimiter {
+            max_requests,
+            window: std::time::Duration::from_secs(window_secs),
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Records a request from `ip`. Returns Err(seconds until the window resets) once the client
+    // has used up its allowance.
+    fn check(&self, ip: IpAddr) -> Result<(), u64> {
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        if clients.len() > RATE_LIMIT_PRUNE_THRESHOLD {
+            clients.retain(|_, state| now.duration_since(state.window_start) < self.window);
+        }
+        let state = clients.entry(ip).or_insert(RateLimitState {
+            window_start: now,
+            count: 0,
+        });
+        if now.duration_since(state.window_start) >= self.window {
+            state.window_start = now;
+            state.count = 0;
+        }
+        if state.count >= self.max_requests {
+            let remaining = self.window - now.duration_since(state.window_start);
+            return Err(remaining.as_secs_f64().ceil().max(1.0) as u64);
+        }
+        state.count += 1;
+        Ok(())
+    }
+}
+
+impl ReportCache {
+    fn new(capacity: NonZeroUsize, ttl: std::time::Duration) -> Self {
+        ReportCache {
+            ttl,
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    // Returns the body cached under `key` unless it has expired.
+    fn get(&self, key: &str) -> Option<String> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        match entries.get(key) {
+            Some((stored_at, body)) if stored_at.elapsed() < self.ttl => Some(body.clone()),
+            Some(_) => {
+                entries.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, key: String, body: String) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.put(key, (Instant::now(), body));
+    }
+
+    fn clear(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+impl Metrics {
+    // Creates every metric and registers it with a fresh registry.
+    fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new();
+        let requests = IntCounterVec::new(
+            Opts::new("youthsync_requests_total", "HTTP requests handled"),
+            &["endpoint", "method", "status"],
+        )?;
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "youthsync_request_duration_seconds",
+                "Time taken to answer HTTP requests",
+            ),
+            &["endpoint"],
+        )?;
+        let pool_idle_connections = IntGauge::new(
+            "youthsync_db_pool_idle_connections",
+            "Idle connections in the database pool",
+        )?;
+        let attendance_inserts = IntCounter::new(
+            "youthsync_attendance_inserts_total",
+            "Attendance records stored",
+        )?;
+        let attendance_insert_errors = IntCounter::new(
+            "youthsync_attendance_insert_errors_total",
+            "Attendance records that were rejected or failed to store",
+        )?;
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(request_duration.clone()))?;
+        registry.register(Box::new(pool_idle_connections.clone()))?;
+        registry.register(Box::new(attendance_inserts.clone()))?;
+        registry.register(Box::new(attendance_insert_errors.clone()))?;
+        Ok(Metrics {
+            registry,
+            requests,
+            request_duration,
+            pool_idle_connections,
+            attendance_inserts,
+            attendance_insert_errors,
+        })
+    }
+}
+
+// Header carrying an API key for server-to-server clients.
+const API_KEY_HEADER: &str = "X-Api-Key";
+
+// NewApiKey is the request body for POST /admin/api-keys.
+#[derive(Debug, Deserialize, ToSchema)]
+struct NewApiKey {
+    label: String, // Who or what the key is for, e.g. "sis-sync"
+}
+
+// CreatedApiKey is returned once when a key is generated; only its hash is kept afterwards.
+#[derive(Debug, Serialize, ToSchema)]
+struct CreatedApiKey {
+    id: i64,
+    label: String,
+    key: String, // Plain key; shown only in this response
+    created_at: String,
+}
+
+// NewUser is the request body for POST /admin/users.
+#[derive(Debug, Deserialize, ToSchema)]
+struct NewUser {
+    username: String,
+    password: String,
+    role: Option<String>, // "admin", "staff" or "viewer", defaults to "staff"
+}
+
+// CreatedUser describes an account created by POST /admin/users; the password is never echoed.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct CreatedUser {
+    id: i64,
+    username: String,
+    role: String,
+    created_at: String,
+}
+
+// Shortest password accepted for a new account.
+const MIN_PASSWORD_CHARS: usize = 8;
+
+// NewWebhook is the request body for POST /admin/webhooks.
+#[derive(Debug, Deserialize, ToSchema)]
+struct NewWebhook {
+    url: String,    // http(s) endpoint that receives the POST
+    secret: String, // Key for the X-YouthSync-Signature HMAC; never returned
+    event: String,  // Only "attendance.low" for now
+    threshold: f64, // Present rate, 0.0-1.0, below which the event fires
+}
+
+// Webhook is a row of the `webhooks` table without its secret.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+struct Webhook {
+    id: i64,
+    url: String,
+    event: String,
+    threshold: f64,
+}
+
+// LowAttendanceEvent is the JSON body POSTed to "attendance.low" webhooks.
+#[derive(Debug, Serialize)]
+struct LowAttendanceEvent<'a> {
+    event: &'a str,
+    webhook_id: i64,
+    date: &'a str,        // "YYYY-MM-DD"
+    attendance_rate: f64, // present / total records for the day, 0.0-1.0
+    threshold: f64,
+    present_count: i32,
+    absent_count: i32,
+    late_count: i32,
+}
+
+// Event fired when a day's present rate drops below a webhook's threshold.
+const WEBHOOK_EVENT_LOW_ATTENDANCE: &str = "attendance.low";
+
+// Header carrying "sha256=<hex HMAC-SHA256 of the body>" on every webhook delivery.
+const WEBHOOK_SIGNATURE_HEADER: &str = "X-YouthSync-Signature";
+
+// How long a webhook endpoint gets to answer before the delivery is abandoned.
+const WEBHOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+// Number of consecutive absences at which a student is flagged in alerts.
+const CONSECUTIVE_ABSENCE_ALERT: u32 = 3;
+
+// ApiError is the JSON body of every error response.
+#[derive(Debug, Serialize, ToSchema)]
+struct ApiError {
+    code: u16,              // HTTP status code, repeated for clients that only see the body
+    message: String,        // Human-readable summary
+    detail: Option<String>, // Underlying cause, when there is one worth reporting
+}
+
+// AppError is the error type returned by handlers; ResponseError turns it into an ApiError body.
+#[derive(Debug)]
+enum AppError {
+    DatabaseError(sqlx::Error),
+    ValidationError(String), // A request body field has an unacceptable value
+    NotFound(String),
+    BadRequest(String),      // Malformed path or query parameters
+    Conflict(String),        // The request clashes with existing data
+    Unauthorized(String),    // Missing or invalid credentials
+    Forbidden(String),       // Valid credentials without the required role
+    PayloadTooLarge(String), // Request body over MAX_BODY_BYTES
+    TooManyRequests(u64),    // Rate limit exceeded; seconds until the client may retry
+    Internal(String),        // Failures outside the database, e.g. CSV encoding
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::DatabaseError(e) => write!(f, "Database error: {}", e),
+            AppError::ValidationError(message)
+            | AppError::NotFound(message)
+            | AppError::BadRequest(message)
+            | AppError::Conflict(message)
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::PayloadTooLarge(message)
+            | AppError::Internal(message) => write!(f, "{}", message),
+            AppError::TooManyRequests(retry_after) => {
+                write!(f, "Rate limit exceeded, retry in {} seconds", retry_after)
+            }
+        }
+    }
+}
+
+impl actix_web::ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::DatabaseError(sqlx::Error::RowNotFound) | AppError::NotFound(_) => {
+                StatusCode::NOT_FOUND
+            }
+            AppError::DatabaseError(sqlx::Error::PoolTimedOut) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::DatabaseError(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::ValidationError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        // Database errors are logged below but their text (constraint names, SQL, file paths)
+        // never reaches the client.
+        let message = match self {
+            AppError::DatabaseError(sqlx::Error::PoolTimedOut) => {
+                "Database is busy, try again shortly".to_string()
+            }
+            AppError::DatabaseError(_) => "Database error".to_string(),
+            other => other.to_string(),
+        };
+        match self {
+            AppError::DatabaseError(sqlx::Error::RowNotFound) => {}
+            AppError::DatabaseError(e) => tracing::error!(error = %e, "database error"),
+            AppError::Internal(message) => tracing::error!(error = %message, "internal error"),
+            _ => {}
+        }
+        let mut builder = HttpResponse::build(self.status_code());
+        if let AppError::TooManyRequests(retry_after) = self {
+            builder.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+        }
+        builder.json(ApiError {
+            code: self.status_code().as_u16(),
+            message,
+            detail: None,
+        })
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        AppError::DatabaseError(e)
+    }
+}
+
+impl From<csv::Error> for AppError {
+    fn from(e: csv::Error) -> Self {
+        AppError::Internal(format!("CSV error: {}", e))
+    }
+}
+
+// JsonConfig error handler: oversized bodies become 413 Payload Too Large, anything else that
+// cannot be parsed a 400, both with the usual ApiError body.
+fn json_error(e: actix_web::error::JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    use actix_web::error::JsonPayloadError;
+    match e {
+        JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+            AppError::PayloadTooLarge(format!("Request body exceeds {} bytes", MAX_BODY_BYTES))
+                .into()
+        }
+        other => AppError::BadRequest(other.to_string()).into(),
+    }
+}
+
+// Returns today's date in the server's local time zone.
+fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+// Program days are weekdays; weekends never break a streak.
+fn is_program_day(day: NaiveDate) -> bool {
+    day.weekday().num_days_from_monday() < 5
+}
+
+// Returns the program day immediately before `day`.
+fn previous_program_day(day: NaiveDate) -> NaiveDate {
+    let mut previous = day - chrono::Duration::days(1);
+    while !is_program_day(previous) {
+        previous -= chrono::Duration::days(1);
+    }
+    previous
+}
+
+// Returns the Monday of the ISO week containing `day`.
+fn week_start(day: NaiveDate) -> NaiveDate {
+    day - chrono::Duration::days(day.weekday().num_days_from_monday() as i64)
+}
+
+// Parses a "YYYY-MM-DD" path or query parameter, rejecting anything else as a bad request.
+fn parse_date(value: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| AppError::BadRequest(format!("Invalid date '{}', expected YYYY-MM-DD", value)))
+}
+
+// Validates a date like parse_date and returns it zero-padded, the only form stored in or matched
+// against attendance.date. chrono also accepts "2025-3-5", " 2025-03-05" and "+2025-03-05", which
+// would otherwise miss range filters, strftime and the one-record-per-session index.
+fn parse_date_param(value: &str) -> Result<String, AppError> {
+    parse_date(value).map(|date| date.format("%Y-%m-%d").to_string())
+}
+
+// Hashes a password into an Argon2 PHC string for the users table.
+fn hash_password(password: &str) -> Result<String, AppError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AppError::Internal(format!("Password hashing failed: {}", e)))
+}
+
+// Verifies a bearer token's signature and expiry and returns its claims.
+fn decode_token(config: &AuthConfig, token: &str) -> Result<Claims, AppError> {
+    jsonwebtoken::decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(config.jwt_secret.as_bytes()),
+        &Validation::default(),
+    )
+    .map(|data| data.claims)
+    .map_err(|e| AppError::Unauthorized(format!("Invalid token: {}", e)))
+}
+
+// Hex-encoded SHA-256 of an API key, as stored in api_keys.key_hash.
+fn hash_api_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+// Signature sent in X-YouthSync-Signature: "sha256=" and the hex HMAC-SHA256 of `body` keyed
+// with the webhook's secret.
+fn webhook_signature(secret: &str, body: &[u8]) -> Result<String, hmac::digest::InvalidLength> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+    mac.update(body);
+    Ok(format!("sha256={:x}", mac.finalize().into_bytes()))
+}
+
+// Looks up an API key by its hash and returns the caller it authenticates.
+async fn authenticate_api_key(pool: &SqlitePool, key: &str) -> Result<AuthGuard, AppError> {
+    let label = sqlx::query_scalar::<_, String>("SELECT label FROM api_keys WHERE key_hash = ?")
+        .bind(hash_api_key(key))
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Invalid API key".to_string()))?;
+    Ok(AuthGuard {
+        subject: format!("api-key:{}", label),
+        role: Role::Staff,
+        method: AuthMethod::ApiKey,
+    })
+}
+
+// Request-ID middleware: tags each request with a fresh UUID, runs it inside a `request` span
+// carrying that ID (so every log line for the request includes it), logs the outcome, and echoes
+// the ID back in an X-Request-Id header. Errors from inner middleware (401, 403, 429, ...) are
+// replaced by their rendered response with the header added.
+async fn request_id(
+    req: ServiceRequest,
+    next: middleware::Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let id = Uuid::new_v4().to_string();
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %req.method(),
+        path = %req.path()
+    );
+    let header = HeaderValue::from_str(&id)
+        .ok()
+        .map(|value| (HeaderName::from_static("x-request-id"), value));
+    match next.call(req).instrument(span.clone()).await {
+        Ok(mut res) => {
+            span.in_scope(|| tracing::info!(status = res.status().as_u16(), "request completed"));
+            if let Some((name, value)) = header {
+                res.headers_mut().insert(name, value);
+            }
+            Ok(res)
+        }
+        Err(e) => {
+            let status = e.as_response_error().status_code().as_u16();
+            span.in_scope(|| tracing::info!(status, "request rejected"));
+            let mut response = e.error_response();
+            if let Some((name, value)) = header {
+                response.headers_mut().insert(name, value);
+            }
+            Err(actix_web::error::InternalError::from_response(e, response).into())
+        }
+    }
+}
+
+// Metrics middleware: counts every request by route pattern, method and status and records how
+// long it took. Requests that match no route share the "unmatched" endpoint label.
+async fn track_metrics(
+    req: ServiceRequest,
+    next: middleware::Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let Some(metrics) = req.app_data::<web::Data<Metrics>>().cloned() else {
+        return next.call(req).await;
+    };
+    let endpoint = req
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = req.method().to_string();
+    let started = Instant::now();
+
+    let result = next.call(req).await;
+    let status = match &result {
+        Ok(res) => res.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    metrics
+        .requests
+        .with_label_values(&[endpoint.as_str(), method.as_str(), status.as_str()])
+        .inc();
+    metrics
+        .request_duration
+        .with_label_values(&[endpoint.as_str()])
+        .observe(started.elapsed().as_secs_f64());
+    result
+}
+
+// Request-timing middleware: logs every request's method, path, status and duration at debug
+// level, and again as a warning when it took longer than Config::slow_request_threshold_ms.
+async fn log_request_timing(
+    req: ServiceRequest,
+    next: middleware::Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let threshold_ms = req
+        .app_data::<web::Data<Config>>()
+        .map_or(DEFAULT_SLOW_REQUEST_THRESHOLD_MS, |config| {
+            config.slow_request_threshold_ms
+        });
+    let method = req.method().clone();
+    let path = req.path().to_string();
+    let started = Instant::now();
+
+    let result = next.call(req).await;
+    let status = match &result {
+        Ok(res) => res.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    let duration_ms = started.elapsed().as_millis() as u64;
+    tracing::debug!(
+        method = %method,
+        path = %path,
+        status = status.as_u16(),
+        duration_ms,
+        "request timing"
+    );
+    if duration_ms > threshold_ms {
+        tracing::warn!(
+            method = %method,
+            path = %path,
+            status = status.as_u16(),
+            duration_ms,
+            threshold_ms,
+            "slow request"
+        );
+    }
+    result
+}
+
+// Rate-limiting middleware: counts write requests (anything but GET, HEAD and OPTIONS) per client
+// IP and answers 429 with a Retry-After header once RateLimiter's allowance is used up.
+async fn rate_limit(
+    req: ServiceRequest,
+    next: middleware::Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let is_read = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if !is_read
+        && let (Some(limiter), Some(addr)) =
+            (req.app_data::<web::Data<RateLimiter>>(), req.peer_addr())
+    {
+        limiter
+            .check(addr.ip())
+            .map_err(AppError::TooManyRequests)?;
+    }
+    next.call(req).await
+}
+
+// `path` without its /v1 prefix, so middleware matches an endpoint however it was called.
+fn unversioned_path(path: &str) -> &str {
+    match path.strip_prefix("/v1") {
+        Some(rest) if rest.starts_with('/') => rest,
+        _ => path,
+    }
+}
+
+// Report cache middleware: clears the ReportCache after any successful write to attendance
+// (everything under /attendance plus the CSV import), so GET /report never serves totals that
+// predate the change.
+async fn invalidate_report_cache(
+    req: ServiceRequest,
+    next: middleware::Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let is_read = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    let path = unversioned_path(req.path());
+    let changes_attendance = !is_read && (path.starts_with("/attendance") || path == "/import/csv");
+    let cache = req.app_data::<web::Data<ReportCache>>().cloned();
+
+    let res = next.call(req).await?;
+    if changes_attendance
+        && res.status().is_success()
+        && let Some(cache) = cache
+    {
+        cache.clear();
+    }
+    Ok(res)
+}
+
+// Cache-Control sent with successful GET /report responses.
+const REPORT_CACHE_CONTROL: &str = "max-age=60, must-revalidate";
+
+// Weak ETag for a response body: the first 16 hex digits of its SHA-256.
+fn weak_etag(body: &[u8]) -> String {
+    let digest = format!("{:x}", Sha256::digest(body));
+    format!("W/\"{}\"", &digest[..16])
+}
+
+// Whether an If-None-Match header value lists `etag` (or is "*"), using weak comparison.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+// Report caching middleware: gives successful GET /report responses a weak ETag over the JSON
+// body plus Cache-Control, and answers 304 Not Modified when If-None-Match already names that
+// ETag. The report is still computed, but unchanged results are not sent again. A Cache-Control
+// the handler set itself, such as max-age=30 on GET /report/today, is kept.
+async fn report_etag(
+    req: ServiceRequest,
+    next: middleware::Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let path = unversioned_path(req.path());
+    let cacheable =
+        req.method() == Method::GET && (path == "/report" || path.starts_with("/report/"));
+    let if_none_match = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let res = next.call(req).await?.map_into_boxed_body();
+    if !cacheable || res.status() != StatusCode::OK {
+        return Ok(res);
+    }
+
+    let (req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+    let body = actix_web::body::to_bytes(body)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to read report body: {}", e)))?;
+    let etag = weak_etag(&body);
+    let headers = res.headers_mut();
+    headers.insert(
+        header::ETAG,
+        HeaderValue::from_str(&etag).map_err(|e| AppError::Internal(e.to_string()))?,
+    );
+    if !headers.contains_key(header::CACHE_CONTROL) {
+        headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(REPORT_CACHE_CONTROL),
+        );
+    }
+
+    let res = if if_none_match.is_some_and(|value| etag_matches(&value, &etag)) {
+        *res.status_mut() = StatusCode::NOT_MODIFIED;
+        res.headers_mut().remove(header::CONTENT_TYPE);
+        res.set_body(BoxBody::new(()))
+    } else {
+        res.set_body(BoxBody::new(body))
+    };
+    Ok(ServiceResponse::new(req, res))
+}
+
+// Authentication middleware: accepts either `Authorization: Bearer <token>` or `X-Api-Key: <key>`
+// and stores the resulting AuthGuard in the request extensions. Writes always need credentials;
+// reads only when AuthConfig::protect_reads is set. POST /auth/token and the health probes are
+// always open and ignore credentials, so a client holding an expired token can still get a new
+// one. Where credentials are optional, invalid ones are ignored rather than rejected. Viewers are
+// limited to reads.
+async fn require_auth(
+    req: ServiceRequest,
+    next: middleware::Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let config = req
+        .app_data::<web::Data<AuthConfig>>()
+        .cloned()
+        .ok_or_else(|| AppError::Internal("Authentication is not configured".to_string()))?;
+    let is_read = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    let open_path = matches!(
+        unversioned_path(req.path()),
+        "/auth/token" | "/health" | "/ready" | "/openapi.json"
+    ) || req.path().starts_with("/docs");
+    if open_path {
+        return next.call(req).await;
+    }
+    let required = !is_read || config.protect_reads;
+
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+    let api_key = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let guard = async {
+        if let Some(token) = token {
+            let claims = decode_token(&config, &token)?;
+            Ok(Some(AuthGuard {
+                subject: claims.sub,
+                role: claims.role,
+                method: AuthMethod::Jwt,
+            }))
+        } else if let Some(key) = api_key {
+            let pool = req
+                .app_data::<web::Data<SqlitePool>>()
+                .cloned()
+                .ok_or_else(|| AppError::Internal("Database pool is not configured".to_string()))?;
+            Ok(Some(authenticate_api_key(pool.get_ref(), &key).await?))
+        } else {
+            Ok::<_, AppError>(None)
+        }
+    }
+    .await;
+    let guard = match guard {
+        Ok(guard) => guard,
+        Err(e @ AppError::Unauthorized(_)) if !required => {
+            tracing::debug!(error = %e, "ignoring invalid credentials on an open read");
+            None
+        }
+        Err(e) => return Err(e.into()),
+    };
+    match guard {
+        Some(guard) if guard.role == Role::Viewer && !is_read => {
+            return Err(AppError::Forbidden(format!(
+                "'{}' is a viewer and may only read",
+                guard.subject
+            ))
+            .into());
+        }
+        Some(guard) => {
+            req.extensions_mut().insert(guard);
+        }
+        None if required => {
+            return Err(
+                AppError::Unauthorized("Missing bearer token or API key".to_string()).into(),
+            );
+        }
+        None => {}
+    }
+    next.call(req).await
+}
+
+// Creates the initial admin account (from ADMIN_USERNAME/ADMIN_PASSWORD) if it does not exist yet.
+async fn seed_admin_user(
+    pool: &SqlitePool,
+    username: &str,
+    password: &str,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT OR IGNORE INTO users (username, password_hash, role) VALUES (?, ?, 'admin')",
+    )
+    .bind(username)
+    .bind(hash_password(password)?)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Applies the journal mode and busy timeout to a freshly opened connection. busy_timeout is a
+// per-connection setting, so this runs for every connection the pool opens.
+async fn apply_sqlite_pragmas(
+    conn: &mut sqlx::SqliteConnection,
+    wal_mode: bool,
+    busy_timeout_ms: u32,
+) -> Result<(), sqlx::Error> {
+    if wal_mode {
+        sqlx::query("PRAGMA journal_mode = WAL")
+            .execute(&mut *conn)
+            .await?;
+    }
+    // PRAGMA values cannot be bound as parameters; the value is a plain integer.
+    sqlx::query(&format!("PRAGMA busy_timeout = {}", busy_timeout_ms))
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+// Logs the journal mode and busy timeout actually in effect. SQLite silently keeps its old
+// journal mode when WAL is unavailable (e.g. on a read-only file system), so a mismatch warns.
+async fn log_sqlite_pragmas(pool: &SqlitePool, wal_mode: bool) -> Result<(), sqlx::Error> {
+    let journal_mode = sqlx::query_scalar::<_, String>("PRAGMA journal_mode")
+        .fetch_one(pool)
+        .await?;
+    let busy_timeout_ms = sqlx::query_scalar::<_, i64>("PRAGMA busy_timeout")
+        .fetch_one(pool)
+        .await?;
+    if wal_mode && !journal_mode.eq_ignore_ascii_case("wal") {
+        tracing::warn!(%journal_mode, busy_timeout_ms, "WAL was requested but is not in effect");
+    } else {
+        tracing::info!(%journal_mode, busy_timeout_ms, "SQLite settings applied");
+    }
+    Ok(())
+}
+
+// Installs the global tracing subscriber. RUST_LOG selects levels, falling back to `log_level`;
+// `json` emits one JSON object per line for production log shippers, otherwise logs pretty-print.
+fn init_tracing(log_level: &str, json: bool) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(log_level));
+    if json {
+        tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .init();
+    } else {
+        tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .pretty()
+            .init();
+    }
+}
+
+// Fraction of `total` represented by `count`, or 0.0 when there is nothing to divide.
+fn rate(count: i64, total: i64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+// Least-squares fit of y on x, returning (slope, r_squared); (0.0, 0.0) with fewer than two points.
+fn linear_regression(points: &[(f64, f64)]) -> (f64, f64) {
+    let n = points.len() as f64;
+    if points.len() < 2 {
+        return (0.0, 0.0);
+    }
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let syy: f64 = points.iter().map(|(_, y)| (y - mean_y).powi(2)).sum();
+    let sxy: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    if sxx == 0.0 {
+        return (0.0, 0.0);
+    }
+    let slope = sxy / sxx;
+    // A flat series is fitted perfectly by a flat line.
+    let r_squared = if syy == 0.0 {
+        1.0
+    } else {
+        sxy * sxy / (sxx * syy)
+    };
+    (slope, r_squared)
+}
+
+// Counts (present, absent, total) records with a date in the inclusive range [start, end].
+async fn count_statuses(
+    pool: &SqlitePool,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<(i64, i64, i64), sqlx::Error> {
+    sqlx::query_as::<_, (i64, i64, i64)>(
+        "SELECT COALESCE(SUM(CASE WHEN status = 'Present' THEN 1 ELSE 0 END), 0), \
+                COALESCE(SUM(CASE WHEN status = 'Absent' THEN 1 ELSE 0 END), 0), \
+                COUNT(*) \
+         FROM active_attendance WHERE date BETWEEN ? AND ?",
+    )
+    .bind(start.format("%Y-%m-%d").to_string())
+    .bind(end.format("%Y-%m-%d").to_string())
+    .fetch_one(pool)
+    .await
+}
+
+// For every student, counts how many of their most recent records in a row have `status`.
+// A student whose latest record has a different status gets a run length of 0.
+async fn trailing_runs(pool: &SqlitePool, status: &str) -> Result<Vec<(i32, u32)>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (i32, String)>(
+        "SELECT student_id, status FROM active_attendance \
+         ORDER BY student_id, date DESC, session DESC",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut runs: Vec<(i32, u32)> = Vec::new();
+    let mut broken = false; // Whether the current student's run has already ended
+    for (student_id, record_status) in rows {
+        if runs.last().map(|(id, _)| *id) != Some(student_id) {
+            runs.push((student_id, 0));
+            broken = false;
+        }
+        if broken {
+            continue;
+        }
+        if record_status == status {
+            if let Some((_, run)) = runs.last_mut() {
+                *run += 1;
+            }
+        } else {
+            broken = true;
+        }
+    }
+    Ok(runs)
+}
+
+// A date (or ISO week) and one of its sessions.
+type SessionKey = (String, Option<String>);
+
+// Computes (current, longest) runs of Present over the program's sessions (every date and session
+// with any attendance, sorted ascending), starting from the student's first record. A session the
+// student was not marked Present for, including one with no record, ends the run; days without
+// any session never do.
+fn present_streaks(
+    sessions: &[SessionKey],
+    records: &HashMap<SessionKey, AttendanceStatus>,
+) -> (u32, u32) {
+    let mut current = 0;
+    let mut longest = 0;
+    let mut started = false;
+    for session in sessions {
+        let status = records.get(session);
+        started |= status.is_some();
+        if !started {
+            continue;
+        }
+        if status == Some(&AttendanceStatus::Present) {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    (current, longest)
+}
+
+// Whether the records between `start` and `end` span more than one session, in which case the
+// daily report breaks each date down by session.
+async fn range_has_sessions(
+    pool: &SqlitePool,
+    start: &str,
+    end: &str,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT COUNT(DISTINCT session) > 1 FROM active_attendance WHERE date BETWEEN ? AND ?",
+    )
+    .bind(start)
+    .bind(end)
+    .fetch_one(pool)
+    .await
+}
+
+// Inclusive date bounds of `year`, or of all time when it is None.
+fn year_bounds(year: Option<i32>) -> (String, String) {
+    match year {
+        Some(year) => (format!("{:04}-01-01", year), format!("{:04}-12-31", year)),
+        None => ("0000-01-01".to_string(), "9999-12-31".to_string()),
+    }
+}
+
+// Per-session totals of `date`, AM first, or nothing when the date has records for at most one
+// session.
+async fn fetch_session_summaries(
+    pool: &SqlitePool,
+    date: &str,
+) -> Result<Vec<SessionSummary>, sqlx::Error> {
+    let sessions = sqlx::query_as::<_, SessionSummary>(
+        "SELECT session, \
+                SUM(CASE WHEN status = 'Present' THEN 1 ELSE 0 END) AS present, \
+                SUM(CASE WHEN status = 'Absent' THEN 1 ELSE 0 END) AS absent, \
+                AVG(CASE WHEN status = 'Present' THEN 1.0 ELSE 0.0 END) AS rate \
+         FROM active_attendance WHERE date = ? AND session IS NOT NULL \
+         GROUP BY session ORDER BY session",
+    )
+    .bind(date)
+    .fetch_all(pool)
+    .await?;
+    Ok(if sessions.len() > 1 {
+        sessions
+    } else {
+        Vec::new()
+    })
+}
+
+// One date's (or date and session's) totals: date, session, present, absent and late counts.
+type DailyTotals = (String, Option<String>, i32, i32, i32);
+
+// Present, absent and late counts per date between `start` and `end`, in date order. With
+// `by_session` each date is split into one row per session (AM first); otherwise the session is
+// None. A negative `limit` returns every row from `offset` on.
+async fn fetch_daily_totals(
+    pool: &SqlitePool,
+    start: &str,
+    end: &str,
+    by_session: bool,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<DailyTotals>, sqlx::Error> {
+    sqlx::query_as::<_, DailyTotals>(
+        "SELECT date, CASE WHEN ?1 THEN session END, \
+                SUM(CASE WHEN status = 'Present' THEN 1 ELSE 0 END), \
+                SUM(CASE WHEN status = 'Absent' THEN 1 ELSE 0 END), \
+                SUM(CASE WHEN status = 'Late' THEN 1 ELSE 0 END) \
+         FROM active_attendance WHERE date BETWEEN ?2 AND ?3 \
+         GROUP BY 1, 2 ORDER BY 1, 2 LIMIT ?4 OFFSET ?5",
+    )
+    .bind(by_session)
+    .bind(start)
+    .bind(end)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+}
+
+// Message returned when a student already has a record for the date and session being inserted.
+const DUPLICATE_ATTENDANCE: &str =
+    "Attendance already recorded for this student on this date and session";
+
+// Whether `e` is the database rejecting a row that breaks a UNIQUE constraint.
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db) if db.is_unique_violation())
+}
+
+// Maps an attendance insert failure to an AppError: a second record for the same student, date
+// and session is a 409 Conflict, anything else a database error.
+fn attendance_insert_error(e: sqlx::Error) -> AppError {
+    if is_unique_violation(&e) {
+        AppError::Conflict(DUPLICATE_ATTENDANCE.to_string())
+    } else {
+        AppError::DatabaseError(e)
+    }
+}
+
+// Inserts one attendance record and its audit_log entry on `conn`, returning the new row id.
+async fn insert_attendance(
+    conn: &mut sqlx::SqliteConnection,
+    record: &Attendance,
+    performed_by: &str,
+) -> Result<i64, sqlx::Error> {
+    let id = sqlx::query(
+        "INSERT INTO attendance \
+             (student_id, date, session, status, recorded_by, facilitator_id, notes) \
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(record.student_id)
+    .bind(&record.date)
+    .bind(record.session.unwrap_or_default())
+    .bind(record.status)
+    .bind(&record.recorded_by)
+    .bind(record.facilitator_id)
+    .bind(&record.notes)
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+    record_audit(
+        &mut *conn,
+        "attendance",
+        id,
+        "INSERT",
+        None,
+        serde_json::to_string(record).ok(),
+        Some(performed_by),
+    )
+    .await?;
+    Ok(id)
+}
+
+// Facilitator credited with attendance entered by `auth`: the one linked to the caller's user
+// account, created (named after the username) the first time that account records attendance.
+// API keys do not belong to a person, so their records have no facilitator.
+async fn facilitator_id(pool: &SqlitePool, auth: &AuthGuard) -> Result<Option<i64>, sqlx::Error> {
+    if auth.method != AuthMethod::Jwt {
+        return Ok(None);
+    }
+    sqlx::query("INSERT OR IGNORE INTO facilitators (name, username) VALUES (?1, ?1)")
+        .bind(&auth.subject)
+        .execute(pool)
+        .await?;
+    sqlx::query_scalar::<_, i64>("SELECT id FROM facilitators WHERE username = ?")
+        .bind(&auth.subject)
+        .fetch_one(pool)
+        .await
+        .map(Some)
+}
+
+// Writes one audit_log row describing a change to a record in `table_name`.
+// `old_value` and `new_value` are JSON snapshots of the record before and after the change.
+async fn record_audit<'c, E>(
+    executor: E,
+    table_name: &str,
+    record_id: i64,
+    action: &str,
+    old_value: Option<String>,
+    new_value: Option<String>,
+    performed_by: Option<&str>,
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::Executor<'c, Database = sqlx::Sqlite>,
+{
+    sqlx::query(
+        "INSERT INTO audit_log (table_name, record_id, action, old_value, new_value, performed_by) \
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(table_name)
+    .bind(record_id)
+    .bind(action)
+    .bind(old_value)
+    .bind(new_value)
+    .bind(performed_by)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// AttendanceRepository is the storage behind the core attendance endpoints: POST /attendance,
+// GET /attendance, GET /attendance/student/{id}, DELETE /attendance/{id} and GET /report.
+// Handlers only see the trait object. Methods return boxed futures so the trait can be used as
+// `dyn`. The other attendance endpoints (bulk insert, CSV import, upsert, restore, exports,
+// /stats and the /report/* family) still query SQLite directly.
+trait AttendanceRepository {
+    // Inserts `record` and its audit_log entry, plus the idempotency key (replaying
+    // ATTENDANCE_RECORDED) if one is given, in one transaction. Returns the new row id.
+    fn insert<'a>(
+        &'a self,
+        record: &'a Attendance,
+        performed_by: &'a str,
+        idempotency_key: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<i64, sqlx::Error>>;
+
+    // Status and body stored for an Idempotency-Key seen before, if any.
+    fn idempotent_response<'a>(
+        &'a self,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<(i64, String)>, sqlx::Error>>;
+
+    // Deletes idempotency keys older than IDEMPOTENCY_KEY_TTL_HOURS, returning how many.
+    fn expire_idempotency_keys(&self) -> BoxFuture<'_, Result<u64, sqlx::Error>>;
+
+    // Number of active records and one page of them ordered by date, optionally only those of
+    // `student_ids`.
+    fn fetch_all<'a>(
+        &'a self,
+        student_ids: Option<&'a [i32]>,
+        limit: i64,
+        offset: i64,
+    ) -> BoxFuture<'a, Result<(i64, Vec<Attendance>), sqlx::Error>>;
+
+    // A student's active records between `start` and `end` in date order, or None when the
+    // student has no records at all.
+    fn fetch_by_student<'a>(
+        &'a self,
+        student_id: i32,
+        start: &'a str,
+        end: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Vec<Attendance>>, sqlx::Error>>;
+
+    // Soft-deletes record `id` and logs it to the audit trail. False if no active record has
+    // that id.
+    fn delete<'a>(
+        &'a self,
+        id: i64,
+        performed_by: &'a str,
+    ) -> BoxFuture<'a, Result<bool, sqlx::Error>>;
+
+    // Number of report rows between `start` and `end` and one page of them. Dates are split by
+    // session only when the range holds more than one session, as in fetch_daily_totals.
+    fn aggregate_daily<'a>(
+        &'a self,
+        start: &'a str,
+        end: &'a str,
+        limit: i64,
+        offset: i64,
+    ) -> BoxFuture<'a, Result<(i64, Vec<DailyTotals>), sqlx::Error>>;
+}
+
+// Attendance records kept in the same SQLite database as everything else.
+struct SqliteRepository {
+    pool: SqlitePool,
+}
+
+impl AttendanceRepository for SqliteRepository {
+    fn insert<'a>(
+        &'a self,
+        record: &'a Attendance,
+        performed_by: &'a str,
+        idempotency_key: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<i64, sqlx::Error>> {
+        Box::pin(async move {
+            let mut tx = self.pool.begin().await?;
+            let id = insert_attendance(&mut tx, record, performed_by).await?;
+            if let Some(key) = idempotency_key {
+                sqlx::query(
+                    "INSERT INTO idempotency_keys (key, response_status, response_body) \
+                     VALUES (?, ?, ?)",
+                )
+                .bind(key)
+                .bind(StatusCode::OK.as_u16())
+                .bind(ATTENDANCE_RECORDED)
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await?;
+            Ok(id)
+        })
+    }
+
+    fn idempotent_response<'a>(
+        &'a self,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<(i64, String)>, sqlx::Error>> {
+        Box::pin(
+            sqlx::query_as::<_, (i64, String)>(
+                "SELECT response_status, response_body FROM idempotency_keys WHERE key = ?",
+            )
+            .bind(key)
+            .fetch_optional(&self.pool),
+        )
+    }
+
+    fn expire_idempotency_keys(&self) -> BoxFuture<'_, Result<u64, sqlx::Error>> {
+        Box::pin(async move {
+            let done =
+                sqlx::query("DELETE FROM idempotency_keys WHERE created_at < datetime('now', ?)")
+                    .bind(format!("-{} hours", IDEMPOTENCY_KEY_TTL_HOURS))
+                    .execute(&self.pool)
+                    .await?;
+            Ok(done.rows_affected())
+        })
+    }
+
+    fn fetch_all<'a>(
+        &'a self,
+        student_ids: Option<&'a [i32]>,
+        limit: i64,
+        offset: i64,
+    ) -> BoxFuture<'a, Result<(i64, Vec<Attendance>), sqlx::Error>> {
+        Box::pin(async move {
+            // SQLite has no array parameters, so the ids travel as a JSON array.
+            let student_ids = student_ids.map(|ids| serde_json::json!(ids).to_string());
+            let total = sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM active_attendance \
+                 WHERE ?1 IS NULL OR student_id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&student_ids)
+            .fetch_one(&self.pool)
+            .await?;
+            let records = sqlx::query_as::<_, Attendance>(
+                "SELECT * FROM active_attendance \
+                 WHERE ?1 IS NULL OR student_id IN (SELECT value FROM json_each(?1)) \
+                 ORDER BY date, id LIMIT ?2 OFFSET ?3",
+            )
+            .bind(&student_ids)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.pool)
+            .await?;
+            Ok((total, records))
+        })
+    }
+
+    fn fetch_by_student<'a>(
+        &'a self,
+        student_id: i32,
+        start: &'a str,
+        end: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Vec<Attendance>>, sqlx::Error>> {
+        Box::pin(async move {
+            let total = sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM active_attendance WHERE student_id = ?",
+            )
+            .bind(student_id)
+            .fetch_one(&self.pool)
+            .await?;
+            if total == 0 {
+                return Ok(None);
+            }
+            sqlx::query_as::<_, Attendance>(
+                "SELECT * FROM active_attendance WHERE student_id = ? AND date BETWEEN ? AND ? \
+                 ORDER BY date ASC",
+            )
+            .bind(student_id)
+            .bind(start)
+            .bind(end)
+            .fetch_all(&self.pool)
+            .await
+            .map(Some)
+        })
+    }
+
+    fn delete<'a>(
+        &'a self,
+        id: i64,
+        performed_by: &'a str,
+    ) -> BoxFuture<'a, Result<bool, sqlx::Error>> {
+        Box::pin(async move {
+            let mut tx = self.pool.begin().await?;
+            let deleted = sqlx::query_as::<_, Attendance>(
+                "UPDATE attendance SET deleted_at = datetime('now') \
+                 WHERE id = ? AND deleted_at IS NULL RETURNING *",
+            )
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+            if let Some(old) = &deleted {
+                record_audit(
+                    &mut *tx,
+                    "attendance",
+                    id,
+                    "DELETE",
+                    serde_json::to_string(old).ok(),
+                    None,
+                    Some(performed_by),
+                )
+                .await?;
+            }
+            tx.commit().await?;
+            Ok(deleted.is_some())
+        })
+    }
+
+    fn aggregate_daily<'a>(
+        &'a self,
+        start: &'a str,
+        end: &'a str,
+        limit: i64,
+        offset: i64,
+    ) -> BoxFuture<'a, Result<(i64, Vec<DailyTotals>), sqlx::Error>> {
+        Box::pin(async move {
+            let by_session = range_has_sessions(&self.pool, start, end).await?;
+            let total = sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM (SELECT 1 FROM active_attendance WHERE date BETWEEN ?2 AND ?3 \
+                 GROUP BY date, CASE WHEN ?1 THEN session END)",
+            )
+            .bind(by_session)
+            .bind(start)
+            .bind(end)
+            .fetch_one(&self.pool)
+            .await?;
+            let days =
+                fetch_daily_totals(&self.pool, start, end, by_session, limit, offset).await?;
+            Ok((total, days))
+        })
+    }
+}
+
+// Column headings of every attendance CSV export.
+const ATTENDANCE_CSV_HEADER: [&str; 7] = [
+    "Student ID",
+    "Date",
+    "Session",
+    "Status",
+    "Notes",
+    "Created At",
+    "Last Updated",
+];
+
+// Rows written between flushes of a streamed export.
+const EXPORT_FLUSH_ROWS: usize = 100;
+
+// Writes one attendance record as a CSV row.
+fn write_attendance_row<W: std::io::Write>(
+    wtr: &mut Writer<W>,
+    record: &Attendance,
+) -> Result<(), csv::Error> {
+    wtr.write_record([
+        record.student_id.to_string(),
+        record.date.clone(),
+        record
+            .session
+            .map(|session| session.as_str().to_string())
+            .unwrap_or_default(),
+        record.status.as_str().to_string(),
+        record.notes.clone().unwrap_or_default(),
+        record.created_at.clone().unwrap_or_default(),
+        record.last_updated.clone().unwrap_or_default(),
+    ])
+}
+
+// Serializes attendance records as CSV with a header row.
+fn write_attendance_csv(records: &[Attendance]) -> Result<Vec<u8>, csv::Error> {
+    // Initialize CSV writer over an in-memory buffer.
+    let mut wtr = Writer::from_writer(vec![]);
+    // Write CSV header row.
+    wtr.write_record(ATTENDANCE_CSV_HEADER)?;
+
+    // Write each record as a new CSV row.
+    for record in records {
+        write_attendance_row(&mut wtr, record)?;
+    }
+    wtr.into_inner().map_err(|e| e.into_error().into())
+}
+
+// Makes a caller-supplied download filename safe: drops path separators and control characters
+// and keeps at most MAX_EXPORT_FILENAME_CHARS characters. Returns None if nothing usable is left.
+fn sanitize_filename(name: &str) -> Option<String> {
+    let cleaned: String = name
+        .chars()
+        .filter(|c| !matches!(c, '/' | '\\') && !c.is_control())
+        .take(MAX_EXPORT_FILENAME_CHARS)
+        .collect();
+    let cleaned = cleaned.trim();
+    if cleaned.is_empty() || cleaned.chars().all(|c| c == '.') {
+        None
+    } else {
+        Some(cleaned.to_string())
+    }
+}
+
+// In-memory writer separating fields with `delimiter` (b',' for CSV, b'\t' for TSV).
+fn delimited_writer(delimiter: u8) -> Writer<Vec<u8>> {
+    WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(vec![])
+}
+
+// Flushes the rows written so far and returns them, leaving `wtr` writing to an empty buffer
+// with the same `delimiter`.
+fn take_csv_chunk(wtr: &mut Writer<Vec<u8>>, delimiter: u8) -> Result<Vec<u8>, csv::Error> {
+    std::mem::replace(wtr, delimited_writer(delimiter))
+        .into_inner()
+        .map_err(|e| e.into_error().into())
+}
+
+// ExportFormat is the file format of a streamed attendance export.
+#[derive(Debug, Clone, Copy)]
+enum ExportFormat {
+    Csv,  // ATTENDANCE_CSV_HEADER row plus one comma-separated row per record
+    Tsv,  // Same columns as Csv, separated by tabs
+    Json, // A single JSON array of Attendance objects
+}
+
+// ExportEncoder incrementally encodes attendance rows in an ExportFormat, handing back whatever
+// has been encoded so far on each `take_chunk`.
+enum ExportEncoder {
+    Csv {
+        wtr: Box<Writer<Vec<u8>>>,
+        delimiter: u8,
+    },
+    Json {
+        buf: Vec<u8>,
+        empty: bool,
+    },
+}
+
+impl ExportEncoder {
+    // Starts an export: the CSV header row or the opening bracket of the JSON array.
+    fn new(format: ExportFormat) -> Result<Self, AppError> {
+        Ok(match format {
+            ExportFormat::Csv | ExportFormat::Tsv => {
+                let delimiter = if let ExportFormat::Tsv = format {
+                    b'\t'
+                } else {
+                    b','
+                };
+                let mut wtr = delimited_writer(delimiter);
+                wtr.write_record(ATTENDANCE_CSV_HEADER)?;
+                ExportEncoder::Csv {
+                    wtr: Box::new(wtr),
+                    delimiter,
+                }
+            }
+            ExportFormat::Json => ExportEncoder::Json {
+                buf: b"[".to_vec(),
+                empty: true,
+            },
+        })
+    }
+
+    // Encodes one record.
+    fn push(&mut self, record: &Attendance) -> Result<(), AppError> {
+        match self {
+            ExportEncoder::Csv { wtr, .. } => write_attendance_row(wtr, record)?,
+            ExportEncoder::Json { buf, empty } => {
+                if !*empty {
+                    buf.push(b',');
+                }
+                serde_json::to_writer(&mut *buf, record)
+                    .map_err(|e| AppError::Internal(format!("JSON error: {}", e)))?;
+                *empty = false;
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the bytes encoded since the previous chunk.
+    fn take_chunk(&mut self) -> Result<Vec<u8>, AppError> {
+        match self {
+            ExportEncoder::Csv { wtr, delimiter } => Ok(take_csv_chunk(wtr, *delimiter)?),
+            ExportEncoder::Json { buf, .. } => Ok(std::mem::take(buf)),
+        }
+    }
+
+    // Closes the export and returns the final chunk.
+    fn finish(mut self) -> Result<Vec<u8>, AppError> {
+        if let ExportEncoder::Json { buf, .. } = &mut self {
+            buf.push(b']');
+        }
+        self.take_chunk()
+    }
+}
+
+// AttendanceSelection narrows the attendance rows an export covers; unset fields select everything.
+#[derive(Debug, Clone, Default)]
+struct AttendanceSelection {
+    student_id: Option<i32>,
+    group_id: Option<i32>, // Students currently assigned to this group
+    range: Option<(String, String)>, // Inclusive "YYYY-MM-DD" bounds
+}
+
+impl AttendanceSelection {
+    // Selection for an export's ?start=/?end= parameters: every row when both are omitted,
+    // otherwise the validated (possibly open-ended) range.
+    fn from_range(range: &DateRangeQuery) -> Result<Self, AppError> {
+        let range = if range.from.is_none() && range.to.is_none() {
+            None
+        } else {
+            Some(range.bounds().map_err(AppError::BadRequest)?)
+        };
+        Ok(AttendanceSelection {
+            range,
+            ..Default::default()
+        })
+    }
+
+    // SELECT statement for the selected rows, ordered by date, student and session. Placeholders
+    // are bound by `bind` in the same order.
+    fn sql(&self) -> String {
+        let mut conditions = Vec::new();
+        if self.student_id.is_some() {
+            conditions.push("student_id = ?");
+        }
+        if self.group_id.is_some() {
+            conditions.push("student_id IN (SELECT id FROM students WHERE group_id = ?)");
+        }
+        if self.range.is_some() {
+            conditions.push("date BETWEEN ? AND ?");
+        }
+        let mut sql = "SELECT * FROM active_attendance".to_string();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY date, student_id, session");
+        sql
+    }
+
+    // Binds the selection's parameters to a query built from `sql()`.
+    fn bind<'q, O>(
+        &'q self,
+        mut query: sqlx::query::QueryAs<'q, sqlx::Sqlite, O, sqlx::sqlite::SqliteArguments<'q>>,
+    ) -> sqlx::query::QueryAs<'q, sqlx::Sqlite, O, sqlx::sqlite::SqliteArguments<'q>> {
+        if let Some(student_id) = self.student_id {
+            query = query.bind(student_id);
+        }
+        if let Some(group_id) = self.group_id {
+            query = query.bind(group_id);
+        }
+        if let Some((from, to)) = &self.range {
+            query = query.bind(from).bind(to);
+        }
+        query
+    }
+}
+
+// Streams the selected attendance records in `format`. A background task reads rows with
+// `fetch()` and sends a chunk down a channel every EXPORT_FLUSH_ROWS rows, so only one chunk is
+// held in memory at a time. A database or encoding error ends the stream early.
+fn stream_attendance_export(
+    pool: SqlitePool,
+    selection: AttendanceSelection,
+    format: ExportFormat,
+) -> impl Stream<Item = Result<web::Bytes, AppError>> {
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<web::Bytes, AppError>>(4);
+    tokio::spawn(
+        async move {
+            let result = async {
+                let mut encoder = ExportEncoder::new(format)?;
+                let sql = selection.sql();
+                let mut rows = selection
+                    .bind(sqlx::query_as::<_, Attendance>(&sql))
+                    .fetch(&pool);
+                let mut written = 0;
+                while let Some(record) = rows.try_next().await? {
+                    encoder.push(&record)?;
+                    written += 1;
+                    if written % EXPORT_FLUSH_ROWS == 0 {
+                        let chunk = encoder.take_chunk()?;
+                        if tx.send(Ok(chunk.into())).await.is_err() {
+                            return Ok(()); // Client went away
+                        }
+                    }
+                }
+                let chunk = encoder.finish()?;
+                let _ = tx.send(Ok(chunk.into())).await;
+                Ok::<_, AppError>(())
+            }
+            .await;
+            if let Err(e) = result {
+                tracing::error!(error = %e, ?format, "export stream failed");
+                let _ = tx.send(Err(e)).await;
+            }
+        }
+        .in_current_span(),
+    );
+    futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|item| (item, rx))
+    })
+}
+
+// Parses a "YYYY-MM-DD HH:MM:SS" UTC timestamp written by SQLite's datetime('now').
+fn parse_sqlite_timestamp(value: &str) -> Option<chrono::DateTime<Local>> {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|t| t.and_utc().with_timezone(&Local))
+}
+
+// Background task: every tick, runs each scheduled export whose next cron occurrence since
+// its last run (or creation) has passed. The file is written into `export_dir` (EXPORT_DIR) and,
+// with a `mailer`, emailed to the schedule's recipient.
+async fn run_scheduled_exports(pool: SqlitePool, export_dir: String, mailer: Option<Mailer>) {
+    let mut interval = tokio::time::interval(EXPORT_SCHEDULER_TICK);
+    loop {
+        interval.tick().await;
+        let schedules =
+            match sqlx::query_as::<_, ScheduledExport>("SELECT * FROM scheduled_exports")
+                .fetch_all(&pool)
+                .await
+            {
+                Ok(schedules) => schedules,
+                Err(e) => {
+                    tracing::error!(error = %e, "failed to load scheduled exports");
+                    continue;
+                }
+            };
+
+        let now = Local::now();
+        for schedule in schedules {
+            let since = schedule
+                .last_run_at
+                .as_deref()
+                .unwrap_or(&schedule.created_at);
+            let cron = match Cron::from_str(&schedule.cron) {
+                Ok(cron) => cron,
+                Err(e) => {
+                    tracing::warn!(schedule_id = schedule.id, cron = %schedule.cron, error = %e, "skipping scheduled export with an invalid cron expression");
+                    continue;
+                }
+            };
+            let Some(since) = parse_sqlite_timestamp(since) else {
+                tracing::warn!(schedule_id = schedule.id, timestamp = %since, "skipping scheduled export with an unreadable run time");
+                continue;
+            };
+            match cron.find_next_occurrence(&since, false) {
+                Ok(next) if next <= now => {}
+                Ok(_) => continue,
+                Err(e) => {
+                    tracing::warn!(schedule_id = schedule.id, cron = %schedule.cron, error = %e, "skipping scheduled export with no next occurrence");
+                    continue;
+                }
+            }
+            if let Err(e) =
+                run_scheduled_export(&pool, &schedule, &export_dir, mailer.as_ref()).await
+            {
+                tracing::error!(schedule_id = schedule.id, error = %e, "scheduled export failed");
+            }
+            // Record the attempt even if it failed, so a broken schedule waits for its next
+            // occurrence instead of retrying every tick.
+            if let Err(e) = sqlx::query(
+                "UPDATE scheduled_exports SET last_run_at = datetime('now') WHERE id = ?",
+            )
+            .bind(schedule.id)
+            .execute(&pool)
+            .await
+            {
+                tracing::error!(schedule_id = schedule.id, error = %e, "failed to record scheduled export run");
+            }
+        }
+    }
+}
+
+// Background task: every IDEMPOTENCY_CLEANUP_TICK, deletes idempotency keys older than
+// IDEMPOTENCY_KEY_TTL_HOURS.
+async fn run_idempotency_key_cleanup(repository: Arc<dyn AttendanceRepository + Send + Sync>) {
+    let mut interval = tokio::time::interval(IDEMPOTENCY_CLEANUP_TICK);
+    loop {
+        interval.tick().await;
+        match repository.expire_idempotency_keys().await {
+            Ok(deleted) if deleted > 0 => {
+                tracing::debug!(deleted, "expired idempotency keys")
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!(error = %e, "failed to expire idempotency keys"),
+        }
+    }
+}
+
+// Produces one scheduled export file and, with a `mailer`, emails it to the schedule's recipient
+// as an attachment.
+async fn run_scheduled_export(
+    pool: &SqlitePool,
+    schedule: &ScheduledExport,
+    export_dir: &str,
+    mailer: Option<&Mailer>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let records = sqlx::query_as::<_, Attendance>("SELECT * FROM active_attendance")
+        .fetch_all(pool)
+        .await?;
+
+    let csv = write_attendance_csv(&records)?;
+
+    tokio::fs::create_dir_all(export_dir).await?;
+    let file_name = format!(
+        "scheduled_{}_{}.csv",
+        schedule.id,
+        Utc::now().format("%Y%m%dT%H%M%SZ")
+    );
+    let path = std::path::Path::new(export_dir).join(&file_name);
+    tokio::fs::write(&path, &csv).await?;
+
+    let Some(mailer) = mailer else {
+        tracing::warn!(
+            schedule_id = schedule.id,
+            recipient = %schedule.recipient,
+            path = %path.display(),
+            "scheduled export written but not emailed: SMTP_HOST is not set"
+        );
+        return Ok(());
+    };
+    let message = Message::builder()
+        .from(mailer.from.clone())
+        .to(schedule.recipient.parse()?)
+        .subject(format!(
+            "Attendance export {}",
+            Local::now().format("%Y-%m-%d")
+        ))
+        .multipart(
+            MultiPart::mixed()
+                .singlepart(SinglePart::plain(format!(
+                    "Attendance records exported on schedule \"{}\" are attached.",
+                    schedule.cron
+                )))
+                .singlepart(Attachment::new(file_name).body(csv, ContentType::parse("text/csv")?)),
+        )?;
+    mailer.transport.send(message).await?;
+    tracing::info!(
+        schedule_id = schedule.id,
+        recipient = %schedule.recipient,
+        path = %path.display(),
+        "scheduled export emailed"
+    );
+    Ok(())
+}
+
+// Maps every student ID in the students table to the student's name.
+async fn student_names(pool: &SqlitePool) -> Result<HashMap<i32, String>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (i32, String)>("SELECT id, name FROM students")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().collect())
+}
+
+// Display name for a student, falling back to the ID for students missing from the roster.
+fn display_name(names: &HashMap<i32, String>, student_id: i32) -> String {
+    names
+        .get(&student_id)
+        .cloned()
+        .unwrap_or_else(|| format!("Student {}", student_id))
+}
+
+// Runs every daily alert check in parallel and merges the results, most urgent first.
+async fn fetch_daily_notifications(pool: &SqlitePool) -> Result<Vec<Notification>, sqlx::Error> {
+    let today = today();
+    let date = today.format("%Y-%m-%d").to_string();
+
+    let (names, absences, first_absences, present_totals, first_dates) = tokio::join!(
+        student_names(pool),
+        trailing_runs(pool, "Absent"),
+        // Students whose earliest absence is today.
+        sqlx::query_scalar::<_, i32>(
+            "SELECT student_id FROM active_attendance WHERE status = 'Absent' \
+             GROUP BY student_id HAVING MIN(date) = ?",
+        )
+        .bind(&date)
+        .fetch_all(pool),
+        // Present totals for students who were present today.
+        sqlx::query_as::<_, (i32, i64)>(
+            "SELECT student_id, COUNT(*) FROM active_attendance WHERE status = 'Present' \
+             GROUP BY student_id HAVING SUM(CASE WHEN date = ? THEN 1 ELSE 0 END) > 0",
+        )
+        .bind(&date)
+        .fetch_all(pool),
+        // First attendance date of every student, used for anniversaries.
+        sqlx::query_as::<_, (i32, String)>(
+            "SELECT student_id, MIN(date) FROM active_attendance GROUP BY student_id",
+        )
+        .fetch_all(pool),
+    );
+    let names = names?;
+
+    let mut notifications: Vec<Notification> = Vec::new();
+    let mut push = |kind: &str, student_id: i32, message: String, priority: &str| {
+        notifications.push(Notification {
+            kind: kind.to_string(),
+            student_id,
+            name: display_name(&names, student_id),
+            message,
+            priority: priority.to_string(),
+        });
+    };
+
+    for (student_id, run) in absences? {
+        if run >= CONSECUTIVE_ABSENCE_ALERT {
+            let message = format!("Absent for the last {} sessions in a row", run);
+            push("consecutive_absences", student_id, message, "high");
+        }
+    }
+    for student_id in first_absences? {
+        push(
+            "first_absence",
+            student_id,
+            "First recorded absence".to_string(),
+            "medium",
+        );
+    }
+    for (student_id, total) in present_totals? {
+        if let Some((tier, _)) = INCENTIVE_TIERS.iter().find(|(_, needed)| *needed == total) {
+            let message = format!("Reached {} tier with {} sessions attended", tier, total);
+            push("milestone", student_id, message, "low");
+        }
+    }
+    for (student_id, first_date) in first_dates? {
+        let Ok(first) = NaiveDate::parse_from_str(&first_date, "%Y-%m-%d") else {
+            continue;
+        };
+        let years = today.year() - first.year();
+        if years > 0 && first.month() == today.month() && first.day() == today.day() {
+            let message = format!("{} year(s) since first attending", years);
+            push("anniversary", student_id, message, "low");
+        }
+    }
+
+    let rank = |priority: &str| match priority {
+        "high" => 0,
+        "medium" => 1,
+        _ => 2,
+    };
+    notifications.sort_by_key(|n| (rank(&n.priority), n.student_id));
+    Ok(notifications)
+}
+
+// Runs every dashboard sub-query in parallel and assembles DashboardMetrics.
+async fn fetch_dashboard_metrics(pool: &SqlitePool) -> Result<DashboardMetrics, sqlx::Error> {
+    let today = today();
+    let since = (today - chrono::Duration::days(30))
+        .format("%Y-%m-%d")
+        .to_string();
+
+    let (enrolled, active, overall, week, absences, perfect, streaks, completeness) = tokio::join!(
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM students").fetch_one(pool),
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(DISTINCT student_id) FROM active_attendance \
+             WHERE status = 'Present' AND date >= ?",
+        )
+        .bind(&since)
+        .fetch_one(pool),
+        sqlx::query_as::<_, (i64, i64)>(
+            "SELECT COALESCE(SUM(CASE WHEN status = 'Present' THEN 1 ELSE 0 END), 0), COUNT(*) \
+             FROM active_attendance",
+        )
+        .fetch_one(pool),
+        count_statuses(pool, week_start(today), today),
+        trailing_runs(pool, "Absent"),
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM ( \
+                 SELECT student_id FROM active_attendance GROUP BY student_id \
+                 HAVING SUM(CASE WHEN status = 'Present' THEN 0 ELSE 1 END) = 0 \
+             )",
+        )
+        .fetch_one(pool),
+        trailing_runs(pool, "Present"),
+        // (records for enrolled students, distinct program days)
+        sqlx::query_as::<_, (i64, i64)>(
+            "SELECT (SELECT COUNT(*) FROM active_attendance \
+                     WHERE student_id IN (SELECT id FROM students)), \
+                    (SELECT COUNT(DISTINCT date) FROM active_attendance)",
+        )
+        .fetch_one(pool),
+    );
+
+    let total_enrolled = enrolled?;
+    let (overall_present, overall_total) = overall?;
+    let (week_present, _, week_total) = week?;
+    let streaks = streaks?;
+    let (enrolled_records, program_days) = completeness?;
+
+    Ok(DashboardMetrics {
+        total_enrolled,
+        active_last_30_days: active?,
+        overall_rate: rate(overall_present, overall_total),
+        this_week_rate: rate(week_present, week_total),
+        at_risk_count: absences?
+            .iter()
+            .filter(|(_, run)| *run >= CONSECUTIVE_ABSENCE_ALERT)
+            .count() as i64,
+        perfect_attendees: perfect?,
+        avg_streak: if streaks.is_empty() {
+            0.0
+        } else {
+            streaks.iter().map(|(_, run)| *run as f64).sum::<f64>() / streaks.len() as f64
+        },
+        data_completeness: rate(enrolled_records, total_enrolled * program_days).min(1.0),
+    })
+}
+
+// Runs the summary card queries in parallel and assembles the result.
+async fn fetch_summary_card(pool: &SqlitePool) -> Result<SummaryCard, sqlx::Error> {
+    let today = today();
+    let week_start = week_start(today);
+    let month_start = today.with_day(1).unwrap_or(today);
+
+    let today_date = today.format("%Y-%m-%d").to_string();
+
+    let (day, sessions, week, month, absences) = tokio::join!(
+        count_statuses(pool, today, today),
+        fetch_session_summaries(pool, &today_date),
+        count_statuses(pool, week_start, today),
+        count_statuses(pool, month_start, today),
+        trailing_runs(pool, "Absent"),
+    );
+    let (today_present, today_absent, today_total) = day?;
+    let (week_present, _, week_total) = week?;
+    let (month_present, _, month_total) = month?;
+    let alerts = absences?
+        .iter()
+        .filter(|(_, run)| *run >= CONSECUTIVE_ABSENCE_ALERT)
+        .count();
+
+    Ok(SummaryCard {
+        today_date,
+        today_present: today_present as i32,
+        today_absent: today_absent as i32,
+        today_rate: rate(today_present, today_total),
+        today_sessions: sessions?,
+        week_rate: rate(week_present, week_total),
+        month_rate: rate(month_present, month_total),
+        alerts: alerts as i32,
+    })
+}
+
+// Root handler: lists the API versions, their base paths and deprecation status.
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "system",
+    summary = "Available API versions",
+    responses(
+        (status = 200, description = "API versions and documentation links", body = ApiIndex),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn index() -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(ApiIndex {
+        name: "YouthSync",
+        versions: &API_VERSIONS,
+        docs: "/docs",
+        openapi: "/openapi.json",
+    }))
+}
+
+// Runs `SELECT 1` against the pool and, when `check_migrations` is set, confirms the sqlx
+// migrations table exists. Gives up after HEALTH_CHECK_TIMEOUT.
+async fn probe_database(pool: &SqlitePool, check_migrations: bool) -> Result<(), String> {
+    let probe = async {
+        sqlx::query("SELECT 1").execute(pool).await?;
+        if check_migrations {
+            let present = sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'",
+            )
+            .fetch_one(pool)
+            .await?
+                > 0;
+            if !present {
+                return Ok(Err("migrations table is missing".to_string()));
+            }
+        }
+        Ok::<_, sqlx::Error>(Ok(()))
+    };
+    match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, probe).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!(
+            "database did not respond within {}s",
+            HEALTH_CHECK_TIMEOUT.as_secs()
+        )),
+    }
+}
+
+// Turns a probe result into 200 {"status":"ok"} or 503 {"status":"degraded"}.
+fn health_response(result: Result<(), String>) -> HttpResponse {
+    match result {
+        Ok(()) => HttpResponse::Ok().json(HealthStatus {
+            status: "ok",
+            db: "ok",
+            detail: None,
+        }),
+        Err(detail) => HttpResponse::ServiceUnavailable().json(HealthStatus {
+            status: "degraded",
+            db: "error",
+            detail: Some(detail),
+        }),
+    }
+}
+
+// GET /health
+// Liveness probe: reports whether the database answers a ping.
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "system",
+    summary = "Liveness probe (database ping)",
+    responses(
+        (status = 200, description = "Database reachable", body = HealthStatus),
+        (status = 503, description = "Database unreachable", body = HealthStatus),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn health_check(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    Ok(health_response(probe_database(pool.get_ref(), false).await))
+}
+
+// GET /ready
+// Readiness probe: like /health, but also requires the migrations table to be present.
+#[utoipa::path(
+    get,
+    path = "/ready",
+    tag = "system",
+    summary = "Readiness probe (ping plus migrations)",
+    responses(
+        (status = 200, description = "Ready to serve traffic", body = HealthStatus),
+        (status = 503, description = "Database unreachable or migrations pending", body = HealthStatus),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn readiness_check(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    Ok(health_response(probe_database(pool.get_ref(), true).await))
+}
+
+// Checks the present rate of `date` after a record with `status` was added to it and notifies
+// every "attendance.low" webhook whose threshold the rate has just dropped below. A day already
+// below a threshold does not notify again until it recovers. Runs in the background after
+// POST /attendance, so failures are only logged.
+async fn notify_low_attendance(
+    pool: SqlitePool,
+    repository: Arc<dyn AttendanceRepository + Send + Sync>,
+    client: reqwest::Client,
+    date: String,
+    status: AttendanceStatus,
+) {
+    let result = async {
+        let webhooks = sqlx::query_as::<_, (i64, String, String, f64)>(
+            "SELECT id, url, secret, threshold FROM webhooks WHERE event = ?",
+        )
+        .bind(WEBHOOK_EVENT_LOW_ATTENDANCE)
+        .fetch_all(&pool)
+        .await?;
+        if webhooks.is_empty() {
+            return Ok(());
+        }
+
+        let (_, rows) = repository
+            .aggregate_daily(&date, &date, i64::MAX, 0)
+            .await?;
+        let (mut present, mut absent, mut late) = (0, 0, 0);
+        for (_, _, p, a, l) in rows {
+            present += p;
+            absent += a;
+            late += l;
+        }
+        let rate = |present: i32, total: i32| present as f64 / total as f64;
+        let total = present + absent + late;
+        if total == 0 {
+            return Ok(()); // The record was deleted again in the meantime
+        }
+        let attendance_rate = rate(present, total);
+        // The rate before this record; a day without records was not below any threshold.
+        let previous_present = present - i32::from(status == AttendanceStatus::Present);
+        let previous_rate = (total > 1).then(|| rate(previous_present, total - 1));
+
+        for (id, url, secret, threshold) in webhooks {
+            let dropped = attendance_rate < threshold
+                && previous_rate.is_none_or(|previous| previous >= threshold);
+            if !dropped {
+                continue;
+            }
+            let body = serde_json::to_vec(&LowAttendanceEvent {
+                event: WEBHOOK_EVENT_LOW_ATTENDANCE,
+                webhook_id: id,
+                date: &date,
+                attendance_rate,
+                threshold,
+                present_count: present,
+                absent_count: absent,
+                late_count: late,
+            })
+            .map_err(|e| AppError::Internal(format!("JSON error: {}", e)))?;
+            tokio::spawn(deliver_webhook(client.clone(), id, url, secret, body));
+        }
+        Ok::<_, AppError>(())
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::warn!(date = %date, error = %e, "low attendance check failed");
+    }
+}
+
+// POSTs `body` to one webhook with its signature, logging the outcome.
+async fn deliver_webhook(
+    client: reqwest::Client,
+    id: i64,
+    url: String,
+    secret: String,
+    body: Vec<u8>,
+) {
+    let signature = match webhook_signature(&secret, &body) {
+        Ok(signature) => signature,
+        Err(e) => {
+            tracing::warn!(webhook_id = id, error = %e, "webhook not signed");
+            return;
+        }
+    };
+    let response = client
+        .post(&url)
+        .header(header::CONTENT_TYPE.as_str(), "application/json")
+        .header(WEBHOOK_SIGNATURE_HEADER, signature)
+        .body(body)
+        .send()
+        .await;
+    match response {
+        Ok(res) if res.status().is_success() => {
+            tracing::info!(webhook_id = id, "webhook delivered")
+        }
+        Ok(res) => {
+            tracing::warn!(
+                webhook_id = id,
+                status = res.status().as_u16(),
+                "webhook rejected"
+            )
+        }
+        Err(e) => tracing::warn!(webhook_id = id, error = %e, "webhook delivery failed"),
+    }
+}
+
+// Rebuilds the response stored for an Idempotency-Key.
+fn idempotent_replay(status: i64, body: String) -> HttpResponse {
+    let status = u16::try_from(status)
+        .ok()
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .unwrap_or(StatusCode::OK);
+    HttpResponse::build(status).body(body)
+}
+
+// POST /attendance
+// Accepts JSON payload to insert a new attendance record into the database. Staff and
+// admins.
+// With an Idempotency-Key header, a successful response is stored alongside the record and
+// replayed for any retry carrying the same key instead of inserting again. When the new record
+// drops the day's present rate below a registered webhook threshold, the webhook is notified.
+#[utoipa::path(
+    post,
+    path = "/v1/attendance",
+    tag = "attendance",
+    summary = "New attendance",
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "UUID; a retry with the same key replays the first response")
+    ),
+    responses(
+        (status = 200, description = "Attendance recorded, or the stored response for a repeated Idempotency-Key", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Caller lacks the required role", body = ApiError),
+        (status = 409, description = "Already recorded for this student, date and session", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn add_attendance(
+    auth: AuthGuard,
+    req: HttpRequest,
+    mut data: web::Json<Attendance>,
+    pool: web::Data<SqlitePool>,
+    repository: web::Data<Arc<dyn AttendanceRepository + Send + Sync>>,
+    metrics: web::Data<Metrics>,
+    webhook_client: web::Data<reqwest::Client>,
+) -> Result<HttpResponse, AppError> {
+    let idempotency_key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| Uuid::parse_str(value.trim()).ok())
+                .ok_or_else(|| {
+                    AppError::BadRequest(format!("{} must be a UUID", IDEMPOTENCY_KEY_HEADER))
+                })?
+                .to_string(),
+        ),
+        None => None,
+    };
+    if let Some(key) = &idempotency_key
+        && let Some((status, body)) = repository.idempotent_response(key).await?
+    {
+        tracing::info!(idempotency_key = %key, "replaying stored response");
+        return Ok(idempotent_replay(status, body));
+    }
+
+    data.date = parse_date_param(&data.date)?;
+    data.facilitator_id = facilitator_id(pool.get_ref(), &auth).await?;
+
+    // Students are always in SQLite. The repository inserts the record, its audit trail entry
+    // and the idempotency key, if any, in one transaction.
+    let result = async {
+        let exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM students WHERE id = ?")
+            .bind(data.student_id)
+            .fetch_one(pool.get_ref())
+            .await?
+            > 0;
+        if !exists {
+            return Ok(false);
+        }
+        repository
+            .insert(&data, &auth.subject, idempotency_key.as_deref())
+            .await
+            .map(|_| true)
+    }
+    .await;
+
+    // A concurrent request with the same key can commit between the lookup above and this
+    // insert, which then fails on the key or on the record it already stored. Replay that
+    // request's response instead of reporting the duplicate.
+    if let (Err(e), Some(key)) = (&result, &idempotency_key)
+        && is_unique_violation(e)
+        && let Some((status, body)) = repository.idempotent_response(key).await?
+    {
+        tracing::info!(idempotency_key = %key, "replaying response stored concurrently");
+        return Ok(idempotent_replay(status, body));
+    }
+
+    if let Ok(true) = result {
+        metrics.attendance_inserts.inc();
+    } else {
+        metrics.attendance_insert_errors.inc();
+    }
+    // Return OK on success; unknown students, duplicates and database failures become AppErrors.
+    if result.map_err(attendance_insert_error)? {
+        tracing::info!(
+            student_id = data.student_id,
+            date = %data.date,
+            status = data.status.as_str(),
+            "attendance recorded"
+        );
+        tokio::spawn(notify_low_attendance(
+            pool.get_ref().clone(),
+            repository.get_ref().clone(),
+            webhook_client.get_ref().clone(),
+            data.date.clone(),
+            data.status,
+        ));
+        Ok(HttpResponse::Ok().body(ATTENDANCE_RECORDED))
+    } else {
+        Err(AppError::BadRequest(format!(
+            "Student {} does not exist",
+            data.student_id
+        )))
+    }
+}
+
+// POST /attendance/bulk
+// Inserts up to MAX_BULK_RECORDS attendance records in one transaction. Every entry is validated
+// first; if any is invalid nothing is inserted and the failures are listed in a 422 response.
+// Staff and admins.
+#[utoipa::path(
+    post,
+    path = "/v1/attendance/bulk",
+    tag = "attendance",
+    summary = "Many records in one transaction",
+    responses(
+        (status = 201, description = "All records inserted", body = BulkInsertResult),
+        (status = 422, description = "Nothing inserted; every invalid entry is listed", body = BulkValidationFailed),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Caller lacks the required role", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn add_attendance_bulk(
+    auth: AuthGuard,
+    data: web::Json<Vec<Attendance>>,
+    pool: web::Data<SqlitePool>,
+    metrics: web::Data<Metrics>,
+) -> Result<HttpResponse, AppError> {
+    let mut records = data.into_inner();
+    if records.is_empty() {
+        return Err(AppError::ValidationError("No records supplied".to_string()));
+    }
+    if records.len() > MAX_BULK_RECORDS {
+        return Err(AppError::ValidationError(format!(
+            "At most {} records may be inserted at once, got {}",
+            MAX_BULK_RECORDS,
+            records.len()
+        )));
+    }
+
+    // Look up every referenced student in one query.
+    let ids: HashSet<i32> = records.iter().map(|r| r.student_id).collect();
+    let placeholders = vec!["?"; ids.len()].join(", ");
+    let sql = format!("SELECT id FROM students WHERE id IN ({})", placeholders);
+    let mut query = sqlx::query_scalar::<_, i32>(&sql);
+    for id in &ids {
+        query = query.bind(id);
+    }
+    let known: HashSet<i32> = query.fetch_all(pool.get_ref()).await?.into_iter().collect();
+
+    // Valid dates are stored zero-padded; invalid ones are reported below.
+    for record in &mut records {
+        if let Ok(date) = parse_date_param(&record.date) {
+            record.date = date;
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let errors: Vec<RecordError> = records
+        .iter()
+        .enumerate()
+        .filter_map(|(index, record)| {
+            let reason = if !known.contains(&record.student_id) {
+                format!("Student {} does not exist", record.student_id)
+            } else if parse_date(&record.date).is_err() {
+                format!("Invalid date '{}', expected YYYY-MM-DD", record.date)
+            } else if !seen.insert((
+                record.student_id,
+                record.date.as_str(),
+                record.session.unwrap_or_default(),
+            )) {
+                format!(
+                    "Duplicate of an earlier record for student {} on {} {}",
+                    record.student_id,
+                    record.date,
+                    record.session.unwrap_or_default().as_str()
+                )
+            } else {
+                return None;
+            };
+            Some(RecordError { index, reason })
+        })
+        .collect();
+    if !errors.is_empty() {
+        metrics.attendance_insert_errors.inc_by(errors.len() as u64);
+        return Ok(
+            HttpResponse::UnprocessableEntity().json(BulkValidationFailed {
+                code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
+                message: format!("{} of {} records are invalid", errors.len(), records.len()),
+                errors,
+            }),
+        );
+    }
+
+    let facilitator_id = facilitator_id(pool.get_ref(), &auth).await?;
+    for record in &mut records {
+        record.facilitator_id = facilitator_id;
+    }
+
+    let result = async {
+        let mut tx = pool.begin().await?;
+        for record in &records {
+            insert_attendance(&mut tx, record, &auth.subject).await?;
+        }
+        tx.commit().await
+    }
+    .await;
+    if let Err(e) = result {
+        // The transaction rolled back, so none of the records were stored.
+        metrics
+            .attendance_insert_errors
+            .inc_by(records.len() as u64);
+        return Err(attendance_insert_error(e));
+    }
+    metrics.attendance_inserts.inc_by(records.len() as u64);
+
+    tracing::info!(inserted = records.len(), "attendance bulk recorded");
+    Ok(HttpResponse::Created().json(BulkInsertResult {
+        inserted: records.len(),
+    }))
+}
+
+// Validates one uploaded CSV row and turns it into an Attendance record entered by `recorded_by`
+// and credited to `facilitator_id`.
+fn parse_import_row(
+    raw: CsvAttendanceRow,
+    known_students: &HashSet<i32>,
+    recorded_by: &str,
+    facilitator_id: Option<i64>,
+) -> Result<Attendance, String> {
+    let student_id = raw
+        .student_id
+        .parse::<i32>()
+        .map_err(|_| format!("Invalid student ID '{}'", raw.student_id))?;
+    if !known_students.contains(&student_id) {
+        return Err(format!("Student {} does not exist", student_id));
+    }
+    let date = parse_date_param(&raw.date)
+        .map_err(|_| format!("Invalid date '{}', expected YYYY-MM-DD", raw.date))?;
+    let status = raw.status.parse::<AttendanceStatus>()?;
+    let session = match raw.session.as_deref().map(str::trim) {
+        None | Some("") => AttendanceSession::default(),
+        Some(session) => session.parse()?,
+    };
+    Ok(Attendance {
+        student_id,
+        date,
+        id: None,
+        status,
+        session: Some(session),
+        recorded_by: Some(recorded_by.to_string()),
+        facilitator_id,
+        notes: raw.notes.filter(|notes| !notes.trim().is_empty()),
+        created_at: None,
+        last_updated: None,
+        deleted_at: None,
+    })
+}
+
+// POST /import/csv
+// Imports attendance from a multipart upload whose `file` field is a CSV in the export format
+// (Student ID, Date, Status). Invalid rows are skipped and reported; all valid rows are
+// inserted in a single transaction, recorded as entered by the caller. Staff and admins.
+#[utoipa::path(
+    post,
+    path = "/v1/import/csv",
+    tag = "attendance",
+    summary = "Multipart CSV upload",
+    request_body(content = String, description = "CSV file in a `file` field", content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Rows imported and rows skipped with reasons", body = ImportResult),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 413, description = "Upload too large", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Caller lacks the required role", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn import_csv(
+    auth: AuthGuard,
+    mut payload: Multipart,
+    pool: web::Data<SqlitePool>,
+    metrics: web::Data<Metrics>,
+) -> Result<HttpResponse, AppError> {
+    let bad_upload = |e: actix_multipart::MultipartError| AppError::BadRequest(e.to_string());
+    let mut file = None;
+    while let Some(mut field) = payload.try_next().await.map_err(bad_upload)? {
+        if field.name() != Some("file") {
+            continue;
+        }
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.try_next().await.map_err(bad_upload)? {
+            if bytes.len() + chunk.len() > MAX_BODY_BYTES {
+                return Err(AppError::PayloadTooLarge(format!(
+                    "Upload exceeds {} bytes",
+                    MAX_BODY_BYTES
+                )));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        file = Some(bytes);
+    }
+    let file = file.ok_or_else(|| AppError::BadRequest("Missing 'file' field".to_string()))?;
+
+    let known: HashSet<i32> = sqlx::query_scalar::<_, i32>("SELECT id FROM students")
+        .fetch_all(pool.get_ref())
+        .await?
+        .into_iter()
+        .collect();
+    let facilitator_id = facilitator_id(pool.get_ref(), &auth).await?;
+
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(file.as_slice());
+    let headers = reader
+        .headers()
+        .map_err(|e| AppError::BadRequest(format!("Unreadable CSV header: {}", e)))?
+        .clone();
+
+    let mut valid = Vec::new();
+    let mut errors = Vec::new();
+    for result in reader.records() {
+        let (row, parsed) = match result {
+            Ok(record) => {
+                let row = record.position().map(|p| p.line()).unwrap_or_default();
+                let parsed = record
+                    .deserialize::<CsvAttendanceRow>(Some(&headers))
+                    .map_err(|e| e.to_string())
+                    .and_then(|raw| parse_import_row(raw, &known, &auth.subject, facilitator_id));
+                (row, parsed)
+            }
+            Err(e) => (
+                e.position().map(|p| p.line()).unwrap_or_default(),
+                Err(e.to_string()),
+            ),
+        };
+        match parsed {
+            Ok(record) => valid.push((row, record)),
+            Err(reason) => errors.push(ImportRowError { row, reason }),
+        }
+    }
+
+    // A failed statement does not abort the SQLite transaction, so rows that clash with an
+    // existing record are skipped and reported while the rest are still inserted.
+    let mut inserted = 0;
+    let mut tx = pool.begin().await?;
+    for (row, record) in &valid {
+        match insert_attendance(&mut tx, record, &auth.subject).await {
+            Ok(_) => inserted += 1,
+            Err(e) if is_unique_violation(&e) => errors.push(ImportRowError {
+                row: *row,
+                reason: DUPLICATE_ATTENDANCE.to_string(),
+            }),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    tx.commit().await?;
+    errors.sort_by_key(|e| e.row);
+
+    metrics.attendance_inserts.inc_by(inserted as u64);
+    metrics.attendance_insert_errors.inc_by(errors.len() as u64);
+
+    tracing::info!(inserted, skipped = errors.len(), "attendance CSV imported");
+    Ok(HttpResponse::Ok().json(ImportResult {
+        inserted,
+        skipped: errors.len(),
+        errors,
+    }))
+}
+
+// GET /report
+// Aggregates attendance by day, optionally limited to `?start=`/`?end=`, and returns one
+// page of DailyReport entries ordered by date. When the range holds both AM and PM records,
+// each date gets one entry per session. Bodies are served from the ReportCache when possible;
+// X-Cache says whether this one was.
+#[utoipa::path(
+    get,
+    path = "/v1/report",
+    tag = "reports",
+    summary = "Aggregated report",
+    params(DateRangeQuery, PaginationQuery),
+    responses(
+        (status = 200, description = "One page of daily totals; X-Cache says whether it came from the cache", body = PaginatedResponse<DailyReport>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_report(
+    range: web::Query<DateRangeQuery>,
+    pagination: web::Query<PaginationQuery>,
+    repository: web::Data<Arc<dyn AttendanceRepository + Send + Sync>>,
+    cache: web::Data<ReportCache>,
+) -> Result<HttpResponse, AppError> {
+    let (start, end) = range.bounds().map_err(AppError::BadRequest)?;
+    let (page, per_page, offset) = pagination.resolve().map_err(AppError::BadRequest)?;
+
+    let key = format!("{}..{}:{}:{}", start, end, page, per_page);
+    if let Some(body) = cache.get(&key) {
+        return Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .insert_header((CACHE_STATUS_HEADER, "HIT"))
+            .body(body));
+    }
+
+    // Count the days (or day sessions) in range and fetch the requested page of daily totals.
+    let (total, days) = repository
+        .aggregate_daily(&start, &end, per_page as i64, offset)
+        .await?;
+    let mut daily_counts: Vec<DailyReport> = Vec::new();
+    for (date, session, present_count, absent_count, late_count) in days {
+        // Parse the stored date string into NaiveDate for formatting.
+        let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+            .map_err(|e| AppError::Internal(format!("Date parse error: {}", e)))?;
+        daily_counts.push(DailyReport {
+            // Format date as "MM-DD-YYYY" for response.
+            date: format!("{:02}-{:02}-{}", date.month(), date.day(), date.year()),
+            session,
+            present_count,
+            absent_count,
+            late_count,
+        });
+    }
+    // Return aggregated report as JSON, keeping a copy for the next identical request.
+    let body = serde_json::to_string(&PaginatedResponse {
+        data: daily_counts,
+        page,
+        per_page,
+        total,
+    })
+    .map_err(|e| AppError::Internal(format!("JSON error: {}", e)))?;
+    cache.insert(key, body.clone());
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header((CACHE_STATUS_HEADER, "MISS"))
+        .body(body))
+}
+
+// GET /stats
+// Summarizes the whole attendance table in a single aggregate query.
+#[utoipa::path(
+    get,
+    path = "/v1/stats",
+    tag = "reports",
+    summary = "Overall data statistics",
+    responses(
+        (status = 200, description = "Totals over the whole attendance table", body = Stats),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_stats(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    let stats = sqlx::query_as::<_, Stats>(
+        "SELECT COUNT(*) AS total_records, \
+                COUNT(DISTINCT student_id) AS unique_students, \
+                COUNT(DISTINCT date) AS unique_dates, \
+                COALESCE(CAST(SUM(CASE WHEN status = 'Present' THEN 1 ELSE 0 END) AS REAL) \
+                         / NULLIF(COUNT(*), 0), 0.0) AS overall_present_rate, \
+                MIN(date) AS earliest_date, \
+                MAX(date) AS latest_date \
+         FROM active_attendance",
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+// GET /report/daily/{date}
+// Aggregates a single day's records into one DailyReport. ?session= counts only that session.
+#[utoipa::path(
+    get,
+    path = "/v1/report/daily/{date}",
+    tag = "reports",
+    summary = "One day's report",
+    params(SessionQuery),
+    responses(
+        (status = 200, description = "The day's totals", body = DailyReport),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 404, description = "Records for the date not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_daily_report(
+    path: web::Path<String>,
+    session: web::Query<SessionQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let day = parse_date(&path)?;
+    let date = day.format("%Y-%m-%d").to_string();
+
+    let records = sqlx::query_as::<_, Attendance>(
+        "SELECT * FROM active_attendance WHERE date = ?1 AND (?2 IS NULL OR session = ?2)",
+    )
+    .bind(&date)
+    .bind(session.session)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    if records.is_empty() {
+        return Err(AppError::NotFound(format!(
+            "No attendance records for {}",
+            date
+        )));
+    }
+
+    let mut report = DailyReport {
+        // Format date as "MM-DD-YYYY" to match GET /report.
+        date: format!("{:02}-{:02}-{}", day.month(), day.day(), day.year()),
+        session: session.session.map(|session| session.as_str().to_string()),
+        present_count: 0,
+        absent_count: 0,
+        late_count: 0,
+    };
+    for record in records {
+        match record.status {
+            AttendanceStatus::Present => report.present_count += 1,
+            AttendanceStatus::Absent => report.absent_count += 1,
+            AttendanceStatus::Late => report.late_count += 1,
+        }
+    }
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// Lists the students recorded with `status` on `date` ("YYYY-MM-DD"), with their names.
+async fn fetch_students_by_status(
+    pool: &SqlitePool,
+    date: &str,
+    status: AttendanceStatus,
+) -> Result<Vec<StudentRecord>, AppError> {
+    let date = parse_date_param(date)?;
+    let students = sqlx::query_as::<_, StudentRecord>(
+        "SELECT a.student_id, s.name FROM active_attendance a \
+         LEFT JOIN students s ON s.id = a.student_id \
+         WHERE a.date = ? AND a.status = ? ORDER BY a.student_id",
+    )
+    .bind(&date)
+    .bind(status)
+    .fetch_all(pool)
+    .await?;
+    Ok(students)
+}
+
+// GET /report/absent/{date}
+// Lists the students marked Absent on one date, with names for follow-up. A day without
+// absences yields an empty array.
+#[utoipa::path(
+    get,
+    path = "/v1/report/absent/{date}",
+    tag = "reports",
+    summary = "Students absent on a date",
+    responses(
+        (status = 200, description = "Students marked absent", body = Vec<StudentRecord>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_absent_students(
+    path: web::Path<String>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let students = fetch_students_by_status(&pool, &path, AttendanceStatus::Absent).await?;
+    Ok(HttpResponse::Ok().json(students))
+}
+
+// GET /report/present/{date}
+// Lists the students marked Present on one date. A day without any yields an empty array.
+#[utoipa::path(
+    get,
+    path = "/v1/report/present/{date}",
+    tag = "reports",
+    summary = "Students present on a date",
+    responses(
+        (status = 200, description = "Students marked present", body = Vec<StudentRecord>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_present_students(
+    path: web::Path<String>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let students = fetch_students_by_status(&pool, &path, AttendanceStatus::Present).await?;
+    Ok(HttpResponse::Ok().json(students))
+}
+
+// GET /report/weekly
+// Aggregates attendance by ISO calendar week, optionally limited to `?start=`/`?end=`. Weeks are
+// split by session when the range holds more than one, as in GET /report.
+#[utoipa::path(
+    get,
+    path = "/v1/report/weekly",
+    tag = "reports",
+    summary = "ISO-week report",
+    params(DateRangeQuery),
+    responses(
+        (status = 200, description = "Totals per ISO week", body = Vec<WeeklyReport>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_weekly_report(
+    range: web::Query<DateRangeQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let (start, end) = range.bounds().map_err(AppError::BadRequest)?;
+    let by_session = range_has_sessions(pool.get_ref(), &start, &end).await?;
+
+    let records = sqlx::query_as::<_, Attendance>(
+        "SELECT * FROM active_attendance WHERE date BETWEEN ? AND ? ORDER BY date",
+    )
+    .bind(start)
+    .bind(end)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    // (week, session) -> (present, absent, students seen)
+    let mut weeks: BTreeMap<SessionKey, (i32, i32, HashSet<i32>)> = BTreeMap::new();
+    for record in records {
+        let Ok(day) = NaiveDate::parse_from_str(&record.date, "%Y-%m-%d") else {
+            continue;
+        };
+        let iso = day.iso_week();
+        let session = record
+            .session
+            .filter(|_| by_session)
+            .map(|session| session.as_str().to_string());
+        let entry = weeks
+            .entry((format!("{}-W{:02}", iso.year(), iso.week()), session))
+            .or_default();
+        match record.status {
+            AttendanceStatus::Present => entry.0 += 1,
+            AttendanceStatus::Absent => entry.1 += 1,
+            AttendanceStatus::Late => (),
+        }
+        entry.2.insert(record.student_id);
+    }
+
+    let report: Vec<WeeklyReport> = weeks
+        .into_iter()
+        .map(
+            |((week, session), (present_count, absent_count, students))| WeeklyReport {
+                week,
+                session,
+                present_count,
+                absent_count,
+                total_students: students.len() as i32,
+            },
+        )
+        .collect();
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// GET /report/monthly
+// Aggregates attendance per calendar month in SQL, optionally limited to `?year=`. Months are
+// split by session when the year holds more than one, as in GET /report.
+#[utoipa::path(
+    get,
+    path = "/v1/report/monthly",
+    tag = "reports",
+    summary = "Month-over-month report",
+    params(MonthlyReportQuery),
+    responses(
+        (status = 200, description = "Totals per month", body = Vec<MonthlyReport>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_monthly_report(
+    query: web::Query<MonthlyReportQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let (start, end) = year_bounds(query.year);
+    let by_session = range_has_sessions(pool.get_ref(), &start, &end).await?;
+
+    let report = sqlx::query_as::<_, MonthlyReport>(
+        "SELECT strftime('%Y-%m', date) AS month, CASE WHEN ?2 THEN session END AS session, \
+                SUM(CASE WHEN status = 'Present' THEN 1 ELSE 0 END) AS present_count, \
+                SUM(CASE WHEN status = 'Absent' THEN 1 ELSE 0 END) AS absent_count, \
+                SUM(CASE WHEN status = 'Late' THEN 1 ELSE 0 END) AS late_count, \
+                AVG(CASE WHEN status = 'Present' THEN 1.0 ELSE 0.0 END) AS attendance_rate \
+         FROM active_attendance \
+         WHERE month IS NOT NULL AND (?1 IS NULL OR strftime('%Y', date) = ?1) \
+         GROUP BY 1, 2 ORDER BY 1, 2",
+    )
+    .bind(query.year.map(|year| format!("{:04}", year)))
+    .bind(by_session)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// GET /report/quarterly
+// Aggregates attendance per calendar quarter in SQL, optionally limited to `?year=`. Quarters
+// without records are omitted; quarters are split by session when the year holds more than one.
+#[utoipa::path(
+    get,
+    path = "/v1/report/quarterly",
+    tag = "reports",
+    summary = "Quarter-over-quarter report",
+    params(QuarterlyReportQuery),
+    responses(
+        (status = 200, description = "Totals per quarter", body = Vec<QuarterlyReport>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_quarterly_report(
+    query: web::Query<QuarterlyReportQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let (start, end) = year_bounds(query.year);
+    let by_session = range_has_sessions(pool.get_ref(), &start, &end).await?;
+
+    let report = sqlx::query_as::<_, QuarterlyReport>(
+        "SELECT strftime('%Y', date) || '-' || \
+                CASE WHEN strftime('%m', date) BETWEEN '01' AND '03' THEN 'Q1' \
+                     WHEN strftime('%m', date) BETWEEN '04' AND '06' THEN 'Q2' \
+                     WHEN strftime('%m', date) BETWEEN '07' AND '09' THEN 'Q3' \
+                     ELSE 'Q4' END AS quarter, \
+                CASE WHEN ?2 THEN session END AS session, \
+                SUM(CASE WHEN status = 'Present' THEN 1 ELSE 0 END) AS present_count, \
+                SUM(CASE WHEN status = 'Absent' THEN 1 ELSE 0 END) AS absent_count, \
+                AVG(CASE WHEN status = 'Present' THEN 1.0 ELSE 0.0 END) AS attendance_rate \
+         FROM active_attendance \
+         WHERE quarter IS NOT NULL AND (?1 IS NULL OR strftime('%Y', date) = ?1) \
+         GROUP BY 1, 2 ORDER BY 1, 2",
+    )
+    .bind(query.year.map(|year| format!("{:04}", year)))
+    .bind(by_session)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// GET /report/year/{year}
+// Summarizes a full calendar year of attendance in one aggregate query.
+#[utoipa::path(
+    get,
+    path = "/v1/report/year/{year}",
+    tag = "reports",
+    summary = "One year's summary",
+    responses(
+        (status = 200, description = "The year's totals", body = YearSummary),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 404, description = "Records for the year not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_year_summary(
+    path: web::Path<i32>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let year = path.into_inner();
+    if !REPORT_YEARS.contains(&year) {
+        return Err(AppError::BadRequest(format!(
+            "year must be between {} and {}",
+            REPORT_YEARS.start(),
+            REPORT_YEARS.end()
+        )));
+    }
+
+    let summary = sqlx::query_as::<_, YearSummary>(
+        "SELECT ?1 AS year, \
+                COALESCE(SUM(CASE WHEN status = 'Present' THEN 1 ELSE 0 END), 0) AS present, \
+                COALESCE(SUM(CASE WHEN status = 'Absent' THEN 1 ELSE 0 END), 0) AS absent, \
+                COALESCE(SUM(CASE WHEN status = 'Late' THEN 1 ELSE 0 END), 0) AS late, \
+                COALESCE(AVG(CASE WHEN status = 'Present' THEN 1.0 ELSE 0.0 END), 0.0) \
+                    AS attendance_rate, \
+                COUNT(DISTINCT date) AS total_days_with_records, \
+                COUNT(DISTINCT student_id) AS unique_students \
+         FROM active_attendance WHERE strftime('%Y', date) = printf('%04d', ?1)",
+    )
+    .bind(year)
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    if summary.total_days_with_records == 0 {
+        return Err(AppError::NotFound("No records for this year".to_string()));
+    }
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+// GET /report/heatmap
+// Returns the present rate of every day in `?year=` that has records, in date order, as
+// calendar-heatmap cells. Days are split by session when the year holds more than one.
+#[utoipa::path(
+    get,
+    path = "/v1/report/heatmap",
+    tag = "reports",
+    summary = "Daily present rates for a year",
+    params(HeatmapQuery),
+    responses(
+        (status = 200, description = "Present rate per recorded day", body = Vec<HeatmapCell>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_heatmap(
+    query: web::Query<HeatmapQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let year = query.year;
+    if !(1000..=9999).contains(&year) {
+        return Err(AppError::BadRequest(
+            "year must be a four-digit year".to_string(),
+        ));
+    }
+
+    let (start, end) = year_bounds(Some(year));
+    let by_session = range_has_sessions(pool.get_ref(), &start, &end).await?;
+
+    let cells = sqlx::query_as::<_, HeatmapCell>(
+        "SELECT date, CASE WHEN ?2 THEN session END AS session, \
+                CAST(SUM(CASE WHEN status = 'Present' THEN 1 ELSE 0 END) AS FLOAT) / COUNT(*) \
+                    AS value \
+         FROM active_attendance WHERE strftime('%Y', date) = ?1 \
+         GROUP BY 1, 2 ORDER BY 1, 2",
+    )
+    .bind(year.to_string())
+    .bind(by_session)
+    .fetch_all(pool.get_ref())
+    .await?;
+    Ok(HttpResponse::Ok().json(cells))
+}
+
+// Aggregates every record dated between `start` and `end` inclusive.
+async fn fetch_period_stats(
+    pool: &SqlitePool,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<PeriodStats, sqlx::Error> {
+    sqlx::query_as::<_, PeriodStats>(
+        "SELECT COUNT(*) AS total_records, \
+                COALESCE(SUM(CASE WHEN status = 'Present' THEN 1 ELSE 0 END), 0) AS present_count, \
+                COALESCE(SUM(CASE WHEN status = 'Absent' THEN 1 ELSE 0 END), 0) AS absent_count, \
+                COALESCE(AVG(CASE WHEN status = 'Present' THEN 1.0 ELSE 0.0 END), 0.0) \
+                    AS attendance_rate \
+         FROM active_attendance WHERE date BETWEEN ? AND ?",
+    )
+    .bind(start.format("%Y-%m-%d").to_string())
+    .bind(end.format("%Y-%m-%d").to_string())
+    .fetch_one(pool)
+    .await
+}
+
+// GET /report/compare
+// Compares attendance over two non-overlapping date ranges, e.g. before and after an
+// intervention. All four bounds are required.
+#[utoipa::path(
+    get,
+    path = "/v1/report/compare",
+    tag = "reports",
+    summary = "Two date ranges side by side",
+    params(CompareQuery),
+    responses(
+        (status = 200, description = "Both periods and the change between them", body = PeriodComparison),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_period_comparison(
+    query: web::Query<CompareQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let period1 = (
+        parse_date(&query.period1_start)?,
+        parse_date(&query.period1_end)?,
+    );
+    let period2 = (
+        parse_date(&query.period2_start)?,
+        parse_date(&query.period2_end)?,
+    );
+    for (name, (start, end)) in [("period1", period1), ("period2", period2)] {
+        if start > end {
+            return Err(AppError::BadRequest(format!(
+                "{name}_start must not be after {name}_end"
+            )));
+        }
+    }
+    if period1.0 <= period2.1 && period2.0 <= period1.1 {
+        return Err(AppError::BadRequest(
+            "period1 and period2 must not overlap".to_string(),
+        ));
+    }
+
+    let (period1, period2) = tokio::try_join!(
+        fetch_period_stats(pool.get_ref(), period1.0, period1.1),
+        fetch_period_stats(pool.get_ref(), period2.0, period2.1),
+    )?;
+    Ok(HttpResponse::Ok().json(PeriodComparison {
+        delta_present_rate: period2.attendance_rate - period1.attendance_rate,
+        period1,
+        period2,
+    }))
+}
+
+// PUT /attendance/upsert
+// Records a student's status for a date and session (AM when omitted), replacing the status if
+// a record already exists.
+// Responds 201 {"action":"inserted"} for a new record and 200 {"action":"updated"} otherwise.
+#[utoipa::path(
+    put,
+    path = "/v1/attendance/upsert",
+    tag = "attendance",
+    summary = "Insert-or-update one record",
+    responses(
+        (status = 200, description = "Existing record updated", body = UpsertResult),
+        (status = 201, description = "New record inserted", body = UpsertResult),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn upsert_attendance(
+    auth: AuthGuard,
+    mut data: web::Json<Attendance>,
+    pool: web::Data<SqlitePool>,
+    metrics: web::Data<Metrics>,
+) -> Result<HttpResponse, AppError> {
+    data.date = parse_date_param(&data.date)?;
+    let facilitator_id = facilitator_id(pool.get_ref(), &auth).await?;
+
+    let result = async {
+        let mut tx = pool.begin().await?;
+        let exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM students WHERE id = ?")
+            .bind(data.student_id)
+            .fetch_one(&mut *tx)
+            .await?
+            > 0;
+        if !exists {
+            return Ok(None);
+        }
+        // last_insert_rowid() is left untouched when the conflict branch runs, so the existing
+        // row is read first; it is also the old value for the audit trail.
+        let session = data.session.unwrap_or_default();
+        let old = sqlx::query(
+            "SELECT * FROM active_attendance WHERE student_id = ? AND date = ? AND session = ?",
+        )
+        .bind(data.student_id)
+        .bind(&data.date)
+        .bind(session)
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|row| Attendance::from_row(&row))
+        .transpose()?;
+        let row = sqlx::query(
+            "INSERT INTO attendance \
+                 (student_id, date, session, status, recorded_by, facilitator_id, notes) \
+             VALUES (?, ?, ?, ?, ?, ?, ?) \
+             ON CONFLICT(student_id, date, session) WHERE deleted_at IS NULL \
+             DO UPDATE SET status = excluded.status, \
+             notes = COALESCE(excluded.notes, notes), last_updated = datetime('now') RETURNING *",
+        )
+        .bind(data.student_id)
+        .bind(&data.date)
+        .bind(session)
+        .bind(data.status)
+        .bind(&data.recorded_by)
+        .bind(facilitator_id)
+        .bind(&data.notes)
+        .fetch_one(&mut *tx)
+        .await?;
+        let new = Attendance::from_row(&row)?;
+        record_audit(
+            &mut *tx,
+            "attendance",
+            row.try_get("id")?,
+            if old.is_some() { "UPDATE" } else { "INSERT" },
+            old.as_ref().and_then(|old| serde_json::to_string(old).ok()),
+            serde_json::to_string(&new).ok(),
+            Some(&auth.subject),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Some(old.is_some()))
+    }
+    .await;
+
+    match result {
+        Ok(Some(false)) => metrics.attendance_inserts.inc(),
+        Ok(Some(true)) => {}
+        _ => metrics.attendance_insert_errors.inc(),
+    }
+    let Some(updated) = result? else {
+        return Err(AppError::BadRequest(format!(
+            "Student {} does not exist",
+            data.student_id
+        )));
+    };
+    let action = if updated { "updated" } else { "inserted" };
+    tracing::info!(
+        student_id = data.student_id,
+        date = %data.date,
+        status = data.status.as_str(),
+        action,
+        "attendance upserted"
+    );
+    let mut response = if updated {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::Created()
+    };
+    Ok(response.json(UpsertResult { action }))
+}
+
+// GET /attendance
+// Returns one page of raw attendance records ordered by date. ?group_id= limits the page to
+// students in that group.
+#[utoipa::path(
+    get,
+    path = "/v1/attendance",
+    tag = "attendance",
+    summary = "Paginated attendance records",
+    params(PaginationQuery, GroupFilterQuery),
+    responses(
+        (status = 200, description = "One page of records", body = PaginatedResponse<Attendance>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn list_attendance(
+    pagination: web::Query<PaginationQuery>,
+    group: web::Query<GroupFilterQuery>,
+    pool: web::Data<SqlitePool>,
+    repository: web::Data<Arc<dyn AttendanceRepository + Send + Sync>>,
+) -> Result<HttpResponse, AppError> {
+    let (page, per_page, offset) = pagination.resolve().map_err(AppError::BadRequest)?;
+
+    // Group membership lives with the students in SQLite; the repository filters by student id.
+    let members = match group.group_id {
+        Some(group_id) => Some(
+            sqlx::query_scalar::<_, i32>("SELECT id FROM students WHERE group_id = ?")
+                .bind(group_id)
+                .fetch_all(pool.get_ref())
+                .await?,
+        ),
+        None => None,
+    };
+    let (total, data) = repository
+        .fetch_all(members.as_deref(), per_page as i64, offset)
+        .await?;
+    Ok(HttpResponse::Ok().json(PaginatedResponse {
+        data,
+        page,
+        per_page,
+        total,
+    }))
+}
+
+// GET /attendance/dates
+// Lists every date with at least one attendance record, oldest first, for date pickers and
+// calendar views. ?student_id= limits the list to that student's records.
+#[utoipa::path(
+    get,
+    path = "/v1/attendance/dates",
+    tag = "attendance",
+    summary = "Distinct recorded dates",
+    params(RecordedDatesQuery),
+    responses(
+        (status = 200, description = "Dates with at least one record, oldest first", body = Vec<String>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn list_recorded_dates(
+    query: web::Query<RecordedDatesQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let dates = sqlx::query_scalar::<_, String>(
+        "SELECT DISTINCT date FROM active_attendance WHERE ?1 IS NULL OR student_id = ?1 \
+         ORDER BY date ASC",
+    )
+    .bind(query.student_id)
+    .fetch_all(pool.get_ref())
+    .await?;
+    Ok(HttpResponse::Ok().json(dates))
+}
+
+// GET /attendance/gaps/{date}
+// Lists the students with no record at all on one date, with names, so forgotten entries can be
+// caught when attendance is entered after the fact. Unlike GET /report/absent/{date}, a gap
+// means no status was ever recorded; a soft-deleted record counts as a gap.
+#[utoipa::path(
+    get,
+    path = "/v1/attendance/gaps/{date}",
+    tag = "attendance",
+    summary = "Students with no record on a date",
+    responses(
+        (status = 200, description = "Students missing a record, by id", body = Vec<StudentRecord>),
+        (status = 400, description = "Invalid date", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_attendance_gaps(
+    path: web::Path<String>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    // Normalised, as chrono also accepts unpadded input such as "2025-3-5".
+    let date = parse_date_param(&path)?;
+
+    let students = sqlx::query_as::<_, StudentRecord>(
+        "SELECT s.id AS student_id, s.name FROM students s \
+         WHERE NOT EXISTS (SELECT 1 FROM active_attendance a \
+                           WHERE a.student_id = s.id AND a.date = ?) \
+         ORDER BY s.id",
+    )
+    .bind(&date)
+    .fetch_all(pool.get_ref())
+    .await?;
+    Ok(HttpResponse::Ok().json(students))
+}
+
+// GET /attendance/student/{id}
+// Returns a student's attendance history in date order, optionally limited to `?start=`/`?end=`.
+#[utoipa::path(
+    get,
+    path = "/v1/attendance/student/{id}",
+    tag = "attendance",
+    summary = "One student's history",
+    params(DateRangeQuery),
+    responses(
+        (status = 200, description = "The student's records in date order", body = Vec<Attendance>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 404, description = "Student has no records not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_student_attendance(
+    path: web::Path<i32>,
+    range: web::Query<DateRangeQuery>,
+    repository: web::Data<Arc<dyn AttendanceRepository + Send + Sync>>,
+) -> Result<HttpResponse, AppError> {
+    let student_id = path.into_inner();
+    let (start, end) = range.bounds().map_err(AppError::BadRequest)?;
+
+    let records = repository
+        .fetch_by_student(student_id, &start, &end)
+        .await?
+        .ok_or_else(|| AppError::NotFound("No attendance records for this student".to_string()))?;
+    Ok(HttpResponse::Ok().json(records))
+}
+
+// GET /attendance/{id}
+// Fetches a single attendance record by its primary key.
+#[utoipa::path(
+    get,
+    path = "/v1/attendance/{id}",
+    tag = "attendance",
+    summary = "One record by id",
+    responses(
+        (status = 200, description = "The record", body = Attendance),
+        (status = 404, description = "Record not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_attendance_record(
+    path: web::Path<i64>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    let record = sqlx::query_as::<_, Attendance>("SELECT * FROM active_attendance WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Attendance record {} not found", id)))?;
+    Ok(HttpResponse::Ok().json(record))
+}
+
+// DELETE /attendance/{id}
+// Soft-deletes one attendance record by its primary key: the row is stamped with deleted_at and
+// left out of every other endpoint until restored. Logged to the audit trail. Admins only.
+#[utoipa::path(
+    delete,
+    path = "/v1/attendance/{id}",
+    tag = "attendance",
+    summary = "One record by id",
+    responses(
+        (status = 200, description = "Record deleted", body = String, content_type = "text/plain"),
+        (status = 404, description = "Record not found", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Caller lacks the required role", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn delete_attendance_record(
+    RequireRole(auth, _): RequireRole<AdminOnly>,
+    path: web::Path<i64>,
+    repository: web::Data<Arc<dyn AttendanceRepository + Send + Sync>>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+
+    if !repository.delete(id, &auth.subject).await? {
+        return Err(AppError::NotFound(format!(
+            "Attendance record {} not found",
+            id
+        )));
+    }
+    tracing::info!(id, "attendance deleted");
+    Ok(HttpResponse::Ok().body("Record deleted"))
+}
+
+// GET /attendance/deleted
+// Returns one page of soft-deleted attendance records, most recently deleted first. Admins only.
+#[utoipa::path(
+    get,
+    path = "/v1/attendance/deleted",
+    tag = "attendance",
+    summary = "Soft-deleted records (admin)",
+    params(PaginationQuery),
+    responses(
+        (status = 200, description = "One page of soft-deleted records", body = PaginatedResponse<Attendance>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Caller lacks the required role", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn list_deleted_attendance(
+    _admin: RequireRole<AdminOnly>,
+    pagination: web::Query<PaginationQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let (page, per_page, offset) = pagination.resolve().map_err(AppError::BadRequest)?;
+
+    let total = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM attendance WHERE deleted_at IS NOT NULL",
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+    let data = sqlx::query_as::<_, Attendance>(
+        "SELECT * FROM attendance WHERE deleted_at IS NOT NULL \
+         ORDER BY deleted_at DESC, id DESC LIMIT ? OFFSET ?",
+    )
+    .bind(per_page)
+    .bind(offset)
+    .fetch_all(pool.get_ref())
+    .await?;
+    Ok(HttpResponse::Ok().json(PaginatedResponse {
+        data,
+        page,
+        per_page,
+        total,
+    }))
+}
+
+// POST /attendance/{id}/restore
+// Undoes a soft delete and returns the restored record. 409 if the student has since been given
+// another record for the same date and session. Admins only.
+#[utoipa::path(
+    post,
+    path = "/v1/attendance/{id}/restore",
+    tag = "attendance",
+    summary = "Undo a soft delete (admin)",
+    responses(
+        (status = 200, description = "The restored record", body = Attendance),
+        (status = 404, description = "Deleted record not found", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Caller lacks the required role", body = ApiError),
+        (status = 409, description = "An active record already holds the slot", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn restore_attendance_record(
+    RequireRole(auth, _): RequireRole<AdminOnly>,
+    path: web::Path<i64>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+
+    let result = async {
+        let mut tx = pool.begin().await?;
+        let Some(old) = sqlx::query_as::<_, Attendance>(
+            "SELECT * FROM attendance WHERE id = ? AND deleted_at IS NOT NULL",
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let restored = sqlx::query_as::<_, Attendance>(
+            "UPDATE attendance SET deleted_at = NULL WHERE id = ? RETURNING *",
+        )
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+        record_audit(
+            &mut *tx,
+            "attendance",
+            id,
+            "UPDATE",
+            serde_json::to_string(&old).ok(),
+            serde_json::to_string(&restored).ok(),
+            Some(&auth.subject),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Some(restored))
+    }
+    .await;
+
+    let restored = result
+        .map_err(attendance_insert_error)?
+        .ok_or_else(|| AppError::NotFound(format!("No deleted attendance record {}", id)))?;
+    tracing::info!(id, "attendance restored");
+    Ok(HttpResponse::Ok().json(restored))
+}
+
+// PUT /attendance/{student_id}/{date}
+// Corrects the status of a student's record for one date and logs the change to the audit trail.
+// ?session= limits the change to that session; otherwise every session of the date is updated.
+#[utoipa::path(
+    put,
+    path = "/v1/attendance/{student_id}/{date}",
+    tag = "attendance",
+    summary = "Corrected status",
+    params(SessionQuery),
+    responses(
+        (status = 200, description = "Record updated", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 404, description = "Record not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn update_attendance_status(
+    auth: AuthGuard,
+    path: web::Path<(i32, String)>,
+    session: web::Query<SessionQuery>,
+    data: web::Json<StatusUpdate>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let (student_id, date) = path.into_inner();
+    let date = parse_date_param(&date)?;
+
+    let result = async {
+        let mut tx = pool.begin().await?;
+        let existing = sqlx::query(
+            "SELECT * FROM active_attendance \
+             WHERE student_id = ?1 AND date = ?2 AND (?3 IS NULL OR session = ?3)",
+        )
+        .bind(student_id)
+        .bind(&date)
+        .bind(session.session)
+        .fetch_all(&mut *tx)
+        .await?;
+        for row in &existing {
+            let old = Attendance::from_row(row)?;
+            let new = Attendance {
+                status: data.status,
+                notes: data.notes.clone().or_else(|| old.notes.clone()),
+                ..Attendance::from_row(row)?
+            };
+            record_audit(
+                &mut *tx,
+                "attendance",
+                row.try_get("id")?,
+                "UPDATE",
+                serde_json::to_string(&old).ok(),
+                serde_json::to_string(&new).ok(),
+                Some(&auth.subject),
+            )
+            .await?;
+        }
+        sqlx::query(
+            "UPDATE attendance SET status = ?1, notes = COALESCE(?2, notes), \
+             last_updated = datetime('now') \
+             WHERE student_id = ?3 AND date = ?4 AND (?5 IS NULL OR session = ?5) \
+             AND deleted_at IS NULL",
+        )
+        .bind(data.status)
+        .bind(&data.notes)
+        .bind(student_id)
+        .bind(&date)
+        .bind(session.session)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(existing.len())
+    }
+    .await;
+
+    if result? == 0 {
+        return Err(AppError::NotFound(
+            "No attendance record for this student and date".to_string(),
+        ));
+    }
+    tracing::info!(student_id, date = %date, status = data.status.as_str(), "attendance updated");
+    Ok(HttpResponse::Ok().body("Record updated"))
+}
+
+// DELETE /attendance/{student_id}/{date}
+// Soft-deletes a student's records for one date, like DELETE /attendance/{id}, and logs them to
+// the audit trail. ?session= limits the removal to that session. Admins only.
+#[utoipa::path(
+    delete,
+    path = "/v1/attendance/{student_id}/{date}",
+    tag = "attendance",
+    summary = "One record",
+    params(SessionQuery),
+    responses(
+        (status = 200, description = "Record deleted", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 404, description = "Record not found", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Caller lacks the required role", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn delete_attendance(
+    RequireRole(auth, _): RequireRole<AdminOnly>,
+    path: web::Path<(i32, String)>,
+    session: web::Query<SessionQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let (student_id, date) = path.into_inner();
+    let date = parse_date_param(&date)?;
+
+    let result = async {
+        let mut tx = pool.begin().await?;
+        let deleted = sqlx::query(
+            "UPDATE attendance SET deleted_at = datetime('now') \
+             WHERE student_id = ?1 AND date = ?2 AND (?3 IS NULL OR session = ?3) \
+             AND deleted_at IS NULL RETURNING *",
+        )
+        .bind(student_id)
+        .bind(&date)
+        .bind(session.session)
+        .fetch_all(&mut *tx)
+        .await?;
+        for row in &deleted {
+            let old = Attendance::from_row(row)?;
+            record_audit(
+                &mut *tx,
+                "attendance",
+                row.try_get("id")?,
+                "DELETE",
+                serde_json::to_string(&old).ok(),
+                None,
+                Some(&auth.subject),
+            )
+            .await?;
+        }
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(deleted.len())
+    }
+    .await;
+
+    if result? == 0 {
+        return Err(AppError::NotFound(
+            "No attendance record for this student and date".to_string(),
+        ));
+    }
+    tracing::info!(student_id, date = %date, "attendance deleted");
+    Ok(HttpResponse::Ok().body("Record deleted"))
+}
+
+// GET /metrics
+// Reports request, database pool and attendance insert metrics in the Prometheus text format.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "system",
+    summary = "Prometheus metrics",
+    responses(
+        (status = 200, description = "Prometheus text exposition", body = String, content_type = "text/plain"),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_metrics(
+    metrics: web::Data<Metrics>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    metrics.pool_idle_connections.set(pool.num_idle() as i64);
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    encoder
+        .encode(&metrics.registry.gather(), &mut body)
+        .map_err(|e| AppError::Internal(format!("Metrics encoding error: {}", e)))?;
+    Ok(HttpResponse::Ok()
+        .content_type(encoder.format_type())
+        .body(body))
+}
+
+// GET /export
+// Exports all attendance records as a CSV file download, streamed so large tables are never
+// held in memory. ?format=tsv switches to tab-separated values. ?start=/?end= limit the date
+// range and ?group_id= to one group's students. The file is named attendance_<today UTC>.csv
+// (or .tsv) unless ?filename= overrides it.
+#[utoipa::path(
+    get,
+    path = "/v1/export",
+    tag = "exports",
+    summary = "CSV export",
+    params(ExportQuery, DateRangeQuery, GroupFilterQuery),
+    responses(
+        (status = 200, description = "Attendance as CSV or TSV", body = String, content_type = "text/csv"),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn export_csv(
+    query: web::Query<ExportQuery>,
+    group: web::Query<GroupFilterQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let (format, content_type, extension) = match query.format.as_deref() {
+        None | Some("csv") => (ExportFormat::Csv, "text/csv", "csv"),
+        Some("tsv") => (ExportFormat::Tsv, "text/tab-separated-values", "tsv"),
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Unsupported format '{}', expected csv or tsv",
+                other
+            )));
+        }
+    };
+    let filename = query
+        .filename
+        .as_deref()
+        .and_then(sanitize_filename)
+        .unwrap_or_else(|| format!("attendance_{}.{}", Utc::now().format("%Y-%m-%d"), extension));
+
+    let selection = AttendanceSelection {
+        group_id: group.group_id,
+        ..AttendanceSelection::from_range(&query.range)?
+    };
+    let stream = stream_attendance_export(pool.get_ref().clone(), selection, format);
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(header::ContentDisposition::attachment(filename))
+        .body(BodyStream::new(stream)))
+}
+
+// GET /export/report
+// Downloads the daily report (the same per-date totals as GET /report) as CSV, one row per
+// date, optionally limited by ?start=/?end=. A Session column splits each date when the range
+// holds both AM and PM records.
+#[utoipa::path(
+    get,
+    path = "/v1/export/report",
+    tag = "exports",
+    summary = "Daily report as CSV",
+    params(DateRangeQuery),
+    responses(
+        (status = 200, description = "Daily totals as CSV", body = String, content_type = "text/csv"),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn export_report_csv(
+    range: web::Query<DateRangeQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let (start, end) = range.bounds().map_err(AppError::BadRequest)?;
+    let by_session = range_has_sessions(pool.get_ref(), &start, &end).await?;
+    let days = fetch_daily_totals(pool.get_ref(), &start, &end, by_session, -1, 0).await?;
+
+    let mut wtr = Writer::from_writer(vec![]);
+    let mut header = vec!["Date", "Present", "Absent", "Late", "AttendanceRate"];
+    if by_session {
+        header.insert(1, "Session");
+    }
+    wtr.write_record(&header)?;
+    for (date, session, present, absent, late) in days {
+        let total = (present + absent + late) as i64;
+        let mut row = vec![
+            date,
+            present.to_string(),
+            absent.to_string(),
+            late.to_string(),
+            format!("{:.4}", rate(present as i64, total)),
+        ];
+        if let Some(session) = session {
+            row.insert(1, session);
+        }
+        wtr.write_record(&row)?;
+    }
+    let data = wtr
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv")
+        .insert_header(header::ContentDisposition::attachment("daily_report.csv"))
+        .body(data))
+}
+
+// GET /export/json
+// Exports attendance records as a JSON array download, optionally limited by ?start=/?end= and
+// ?group_id=.
+#[utoipa::path(
+    get,
+    path = "/v1/export/json",
+    tag = "exports",
+    summary = "JSON export",
+    params(DateRangeQuery, GroupFilterQuery),
+    responses(
+        (status = 200, description = "Attendance as a JSON array", body = Vec<Attendance>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn export_json(
+    query: web::Query<DateRangeQuery>,
+    group: web::Query<GroupFilterQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let selection = AttendanceSelection {
+        group_id: group.group_id,
+        ..AttendanceSelection::from_range(&query)?
+    };
+    let stream = stream_attendance_export(pool.get_ref().clone(), selection, ExportFormat::Json);
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header(header::ContentDisposition::attachment("attendance.json"))
+        .body(BodyStream::new(stream)))
+}
+
+// GET /export/student/{id}
+// Downloads one student's attendance as CSV in the same format as /export.
+#[utoipa::path(
+    get,
+    path = "/v1/export/student/{id}",
+    tag = "exports",
+    summary = "One student's CSV",
+    responses(
+        (status = 200, description = "One student's records as CSV", body = String, content_type = "text/csv"),
+        (status = 404, description = "Student not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn export_student_csv(
+    path: web::Path<i32>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let student_id = path.into_inner();
+    let records =
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM active_attendance WHERE student_id = ?")
+            .bind(student_id)
+            .fetch_one(pool.get_ref())
+            .await?;
+    if records == 0 {
+        return Err(AppError::NotFound(format!(
+            "No attendance records for student {}",
+            student_id
+        )));
+    }
+
+    let selection = AttendanceSelection {
+        student_id: Some(student_id),
+        ..Default::default()
+    };
+    let stream = stream_attendance_export(pool.get_ref().clone(), selection, ExportFormat::Csv);
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv")
+        .insert_header(header::ContentDisposition::attachment(format!(
+            "student_{}_attendance.csv",
+            student_id
+        )))
+        .body(BodyStream::new(stream)))
+}
+
+// GET /report/attendance-summary-card
+// Returns a compact SummaryCard JSON object for mobile app home screens.
+#[utoipa::path(
+    get,
+    path = "/v1/report/attendance-summary-card",
+    tag = "reports",
+    summary = "Mobile summary card",
+    responses(
+        (status = 200, description = "Headline numbers for the mobile card", body = SummaryCard),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_summary_card(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    let card = fetch_summary_card(pool.get_ref()).await?;
+    Ok(HttpResponse::Ok().json(card))
+}
+
+// PATCH /students/bulk
+// Updates the grade and/or group of several students inside a single transaction.
+#[utoipa::path(
+    patch,
+    path = "/v1/students/bulk",
+    tag = "students",
+    summary = "Several students",
+    responses(
+        (status = 200, description = "Number of students updated", body = BulkUpdateResult),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 422, description = "Validation failed", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn bulk_update_students(
+    auth: AuthGuard,
+    data: web::Json<BulkStudentUpdate>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    if data.ids.is_empty() {
+        return Err(AppError::ValidationError(
+            "No student IDs supplied".to_string(),
+        ));
+    }
+    ensure_group_exists(pool.get_ref(), data.updates.group_id).await?;
+
+    // One placeholder per ID for the IN (...) clause.
+    let placeholders = vec!["?"; data.ids.len()].join(", ");
+    let select_sql = format!("SELECT * FROM students WHERE id IN ({})", placeholders);
+    let update_sql = format!(
+        "UPDATE students SET grade = COALESCE(?, grade), group_id = COALESCE(?, group_id) \
+         WHERE id IN ({}) RETURNING *",
+        placeholders
+    );
+
+    // Each changed student gets its own audit entry with before and after snapshots.
+    let result = async {
+        let mut tx = pool.begin().await?;
+        let mut select = sqlx::query_as::<_, Student>(&select_sql);
+        for id in &data.ids {
+            select = select.bind(id);
+        }
+        let old: HashMap<i32, Student> = select
+            .fetch_all(&mut *tx)
+            .await?
+            .into_iter()
+            .map(|student| (student.id, student))
+            .collect();
+        let mut update = sqlx::query_as::<_, Student>(&update_sql)
+            .bind(&data.updates.grade)
+            .bind(data.updates.group_id);
+        for id in &data.ids {
+            update = update.bind(id);
+        }
+        let updated = update.fetch_all(&mut *tx).await?;
+        for student in &updated {
+            record_audit(
+                &mut *tx,
+                "students",
+                student.id as i64,
+                "UPDATE",
+                old.get(&student.id)
+                    .and_then(|old| serde_json::to_string(old).ok()),
+                serde_json::to_string(student).ok(),
+                Some(&auth.subject),
+            )
+            .await?;
+        }
+        tx.commit().await?;
+        Ok::<u64, sqlx::Error>(updated.len() as u64)
+    }
+    .await;
+
+    let updated = result?;
+    tracing::info!(updated, "students bulk updated");
+    Ok(HttpResponse::Ok().json(BulkUpdateResult { updated }))
+}
+
+// GET /report/attendance-by-staff-member
+// Compares recording patterns across staff members and flags outlying present rates.
+#[utoipa::path(
+    get,
+    path = "/v1/report/attendance-by-staff-member",
+    tag = "reports",
+    summary = "Staff recording patterns",
+    responses(
+        (status = 200, description = "Recording habits per staff member", body = Vec<StaffRecordingPattern>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_staff_recording_patterns(
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    // Aggregate per (staff, date) first so every recorded day carries equal weight.
+    let rows = sqlx::query_as::<_, (String, i64, f64, f64)>(
+        "WITH daily AS ( \
+             SELECT recorded_by, date, COUNT(*) AS records, \
+                    AVG(CASE WHEN status = 'Present' THEN 1.0 ELSE 0.0 END) AS present_rate \
+             FROM active_attendance WHERE recorded_by IS NOT NULL \
+             GROUP BY recorded_by, date \
+         ) \
+         SELECT recorded_by, COUNT(*), AVG(present_rate), AVG(records) \
+         FROM daily GROUP BY recorded_by ORDER BY recorded_by",
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    // Population mean and standard deviation of the per-staff present rates.
+    let n = rows.len() as f64;
+    let mean = rows.iter().map(|r| r.2).sum::<f64>() / n.max(1.0);
+    let variance = rows.iter().map(|r| (r.2 - mean).powi(2)).sum::<f64>() / n.max(1.0);
+    let std_dev = variance.sqrt();
+
+    let patterns: Vec<StaffRecordingPattern> = rows
+        .into_iter()
+        .map(
+            |(staff_id, dates_recorded, avg_present_rate, avg_records_per_day)| {
+                StaffRecordingPattern {
+                    staff_id,
+                    dates_recorded,
+                    avg_present_rate,
+                    avg_records_per_day,
+                    outlier: std_dev > 0.0 && (avg_present_rate - mean).abs() > 2.0 * std_dev,
+                }
+            },
+        )
+        .collect();
+    Ok(HttpResponse::Ok().json(patterns))
+}
+
+// Escapes an iCalendar TEXT value (SUMMARY, DESCRIPTION) per RFC 5545 section 3.3.11.
+fn ics_text(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Encodes a quoted iCalendar parameter value such as CN. Inside quotes `,` and `;` are literal;
+// newlines, double quotes and carets use the RFC 6868 caret escapes.
+fn ics_param(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '^' => escaped.push_str("^^"),
+            '\n' => escaped.push_str("^n"),
+            '"' => escaped.push_str("^'"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Appends one content line, folded so that no line exceeds 75 octets (RFC 5545 section 3.1).
+fn push_ics_line(ics: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            ics.push_str("\r\n ");
+            width = 1;
+        }
+        ics.push(c);
+        width += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+// GET /students/{id}/attendance-export-ics
+// Exports every "Present" record for one student as an iCalendar (.ics) file, one all-day event
+// per session attended, with the record's notes as the event description.
+#[utoipa::path(
+    get,
+    path = "/v1/students/{id}/attendance-export-ics",
+    tag = "exports",
+    summary = "Student calendar",
+    responses(
+        (status = 200, description = "The student's sessions as an iCalendar file", body = String, content_type = "text/calendar"),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn export_student_ics(
+    path: web::Path<i32>,
+    config: web::Data<Config>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let student_id = path.into_inner();
+    let records = sqlx::query_as::<_, (String, Option<AttendanceSession>, Option<String>)>(
+        "SELECT date, session, notes FROM active_attendance \
+         WHERE student_id = ? AND status = 'Present' ORDER BY date, session",
+    )
+    .bind(student_id)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    // Program name shown as the event organizer.
+    let program_name = &config.program_name;
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+    // iCalendar requires CRLF line endings.
+    let mut ics = String::from(
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//YouthSync//Attendance Export//EN\r\n",
+    );
+    for (date, session, notes) in records {
+        // Skip rows whose date cannot be expressed as an all-day event.
+        let Ok(day) = NaiveDate::parse_from_str(&date, "%Y-%m-%d") else {
+            continue;
+        };
+        // A student has at most one record per date and session, so this UID is unique.
+        let session = session.unwrap_or_default().as_str();
+        push_ics_line(&mut ics, "BEGIN:VEVENT");
+        push_ics_line(
+            &mut ics,
+            &format!("UID:{}-{}-{}@youthsync", student_id, date, session),
+        );
+        push_ics_line(&mut ics, &format!("DTSTAMP:{}", stamp));
+        push_ics_line(
+            &mut ics,
+            &format!("DTSTART;VALUE=DATE:{}", day.format("%Y%m%d")),
+        );
+        push_ics_line(
+            &mut ics,
+            &format!(
+                "SUMMARY:{}",
+                ics_text(&format!("Attended {}", program_name))
+            ),
+        );
+        if let Some(notes) = notes.filter(|notes| !notes.trim().is_empty()) {
+            push_ics_line(&mut ics, &format!("DESCRIPTION:{}", ics_text(&notes)));
+        }
+        push_ics_line(
+            &mut ics,
+            &format!(
+                "ORGANIZER;CN=\"{}\":mailto:noreply@youthsync.invalid",
+                ics_param(program_name)
+            ),
+        );
+        push_ics_line(&mut ics, "END:VEVENT");
+    }
+    ics.push_str("END:VCALENDAR\r\n");
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .insert_header((
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"student_{}_attendance.ics\"",
+                student_id
+            ),
+        ))
+        .body(ics))
+}
+
+// GET /report/attendance-goal-met-days
+// Lists the dates on which every enrolled student was marked "Present" in every session held.
+#[utoipa::path(
+    get,
+    path = "/v1/report/attendance-goal-met-days",
+    tag = "reports",
+    summary = "Perfect-attendance days",
+    params(DateRangeQuery),
+    responses(
+        (status = 200, description = "Dates on which every recorded student was present", body = Vec<String>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_perfect_days(
+    query: web::Query<DateRangeQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let (from, to) = query.bounds().map_err(AppError::BadRequest)?;
+
+    // Each session held that day must have every student present. A day with no enrolled
+    // students cannot be perfect, hence the extra > 0 check.
+    let dates = sqlx::query_scalar::<_, String>(
+        "SELECT date FROM ( \
+             SELECT date, COUNT(DISTINCT CASE WHEN status = 'Present' THEN student_id END) AS present \
+             FROM active_attendance WHERE date BETWEEN ? AND ? \
+             GROUP BY date, session) \
+         GROUP BY date \
+         HAVING MIN(present) = (SELECT COUNT(*) FROM students) \
+            AND (SELECT COUNT(*) FROM students) > 0 \
+         ORDER BY date",
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(dates))
+}
+
+// GET /report/today (also served at /report/daily-attendance-summary)
+// Returns today's TodaySummary with a short Cache-Control lifetime; the URL stays the same
+// but the date rolls over daily, so cached copies naturally expire with the day.
+#[utoipa::path(
+    get,
+    path = "/v1/report/today",
+    tag = "reports",
+    summary = "Today's summary",
+    responses(
+        (status = 200, description = "Today's totals", body = TodaySummary),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_today_summary(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    let today = today();
+    let date = today.format("%Y-%m-%d").to_string();
+
+    let (counts, sessions, not_recorded) = tokio::join!(
+        count_statuses(pool.get_ref(), today, today),
+        fetch_session_summaries(pool.get_ref(), &date),
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM students \
+             WHERE id NOT IN (SELECT student_id FROM active_attendance WHERE date = ?)",
+        )
+        .bind(&date)
+        .fetch_one(pool.get_ref()),
+    );
+
+    let (present, absent, total) = counts?;
+    let not_recorded = not_recorded?;
+    Ok(HttpResponse::Ok()
+        .insert_header(("Cache-Control", "max-age=30"))
+        .json(TodaySummary {
+            date,
+            present: present as i32,
+            absent: absent as i32,
+            rate: rate(present, total),
+            not_recorded: not_recorded as i32,
+            sessions: sessions?,
+        }))
+}
+
+// GET /report/attendance-streak-distribution
+// Computes each student's current "Present" streak and reports how the streaks are distributed.
+#[utoipa::path(
+    get,
+    path = "/v1/report/attendance-streak-distribution",
+    tag = "reports",
+    summary = "Streak distribution",
+    responses(
+        (status = 200, description = "How many students hold each streak length", body = StreakDistribution),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_streak_distribution(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    let runs = trailing_runs(pool.get_ref(), "Present").await?;
+
+    let mut streaks: Vec<u32> = runs.into_iter().map(|(_, run)| run).collect();
+    streaks.sort_unstable();
+
+    // Streaks are sorted, so equal lengths are adjacent.
+    let mut distribution: Vec<StreakBucket> = Vec::new();
+    for &streak in &streaks {
+        match distribution.last_mut() {
+            Some(bucket) if bucket.streak_length == streak => bucket.student_count += 1,
+            _ => distribution.push(StreakBucket {
+                streak_length: streak,
+                student_count: 1,
+            }),
+        }
+    }
+
+    let count = streaks.len();
+    let mean_streak = if count == 0 {
+        0.0
+    } else {
+        streaks.iter().map(|&s| s as f64).sum::<f64>() / count as f64
+    };
+    let median_streak = match count {
+        0 => 0.0,
+        n if n % 2 == 1 => streaks[n / 2] as f64,
+        n => (streaks[n / 2 - 1] + streaks[n / 2]) as f64 / 2.0,
+    };
+
+    Ok(HttpResponse::Ok().json(StreakDistribution {
+        distribution,
+        mean_streak,
+        max_streak: streaks.last().copied().unwrap_or(0),
+        median_streak,
+    }))
+}
+
+// GET /report/attendance-by-zip-code
+// Groups attendance by the students' home ZIP code to show which communities are served.
+#[utoipa::path(
+    get,
+    path = "/v1/report/attendance-by-zip-code",
+    tag = "reports",
+    summary = "Rates by ZIP code",
+    responses(
+        (status = 200, description = "Present rate per ZIP code", body = Vec<ZipAttendance>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_attendance_by_zip(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    let rows = sqlx::query_as::<_, (Option<String>, i64, f64)>(
+        "SELECT s.address_zip, COUNT(DISTINCT a.student_id), \
+                AVG(CASE WHEN a.status = 'Present' THEN 1.0 ELSE 0.0 END) \
+         FROM active_attendance a JOIN students s ON s.id = a.student_id \
+         GROUP BY s.address_zip ORDER BY s.address_zip",
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let report: Vec<ZipAttendance> = rows
+        .into_iter()
+        .map(|(zip_code, student_count, present_rate)| ZipAttendance {
+            zip_code,
+            student_count,
+            present_rate,
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// GET /report/daily-notifications
+// Returns every alert that should be sent today, sorted by priority.
+#[utoipa::path(
+    get,
+    path = "/v1/report/daily-notifications",
+    tag = "reports",
+    summary = "Today's alerts",
+    responses(
+        (status = 200, description = "Alerts for today", body = Vec<Notification>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_daily_notifications(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    let notifications = fetch_daily_notifications(pool.get_ref()).await?;
+    Ok(HttpResponse::Ok().json(notifications))
+}
+
+// GET /students/at-risk
+// Lists students with at least `?threshold=` absences (default 3) on or after `?since=`, most
+// absences first. Without ?since= every record counts.
+#[utoipa::path(
+    get,
+    path = "/v1/students/at-risk",
+    tag = "students",
+    summary = "Students over an absence threshold",
+    params(AtRiskQuery),
+    responses(
+        (status = 200, description = "Students over the absence threshold", body = Vec<AtRiskStudent>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_at_risk_students(
+    query: web::Query<AtRiskQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let threshold = query.threshold.unwrap_or(DEFAULT_AT_RISK_THRESHOLD);
+    if threshold < 1 {
+        return Err(AppError::BadRequest(
+            "threshold must be at least 1".to_string(),
+        ));
+    }
+    let since = match &query.since {
+        Some(since) => parse_date_param(since)?,
+        None => "0000-01-01".to_string(),
+    };
+
+    let students = sqlx::query_as::<_, AtRiskStudent>(
+        "SELECT a.student_id, s.name, a.absent_count FROM ( \
+             SELECT student_id, COUNT(*) AS absent_count FROM active_attendance \
+             WHERE date >= ? AND status = 'Absent' \
+             GROUP BY student_id HAVING absent_count >= ? \
+         ) a LEFT JOIN students s ON s.id = a.student_id \
+         ORDER BY a.absent_count DESC, a.student_id",
+    )
+    .bind(since)
+    .bind(threshold)
+    .fetch_all(pool.get_ref())
+    .await?;
+    Ok(HttpResponse::Ok().json(students))
+}
+
+// GET /students/{id}/stats
+// Reports a student's record counts by status and present rate, optionally limited to
+// `?start=`/`?end=`. Responds 404 when the student has no attendance records at all.
+#[utoipa::path(
+    get,
+    path = "/v1/students/{id}/stats",
+    tag = "students",
+    summary = "Attendance counts and rate",
+    params(DateRangeQuery),
+    responses(
+        (status = 200, description = "Counts and present rate", body = StudentStats),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 404, description = "Student not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_student_stats(
+    path: web::Path<i32>,
+    range: web::Query<DateRangeQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let student_id = path.into_inner();
+    let (start, end) = range.bounds().map_err(AppError::BadRequest)?;
+
+    // COUNT(*) spans every record so a student with none can be told apart from an empty range.
+    let (all_records, total_days, present, absent, late) =
+        sqlx::query_as::<_, (i64, i64, i64, i64, i64)>(
+            "SELECT COUNT(*), \
+                    COALESCE(SUM(in_range), 0), \
+                    COALESCE(SUM(in_range AND status = 'Present'), 0), \
+                    COALESCE(SUM(in_range AND status = 'Absent'), 0), \
+                    COALESCE(SUM(in_range AND status = 'Late'), 0) \
+             FROM (SELECT status, date BETWEEN ? AND ? AS in_range \
+                   FROM active_attendance WHERE student_id = ?)",
+        )
+        .bind(&start)
+        .bind(&end)
+        .bind(student_id)
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if all_records == 0 {
+        return Err(AppError::NotFound(
+            "No attendance records for this student".to_string(),
+        ));
+    }
+    Ok(HttpResponse::Ok().json(StudentStats {
+        student_id,
+        total_days,
+        present,
+        absent,
+        late,
+        attendance_rate: rate(present, total_days),
+    }))
+}
+
+// GET /students/{id}/streak
+// Reports the student's current and longest runs of consecutive Present sessions. Each AM or PM
+// session with attendance for some student counts, so weekends and holidays never break a
+// streak. Responds 404 when the student has no attendance records.
+#[utoipa::path(
+    get,
+    path = "/v1/students/{id}/streak",
+    tag = "students",
+    summary = "Current and longest streaks",
+    responses(
+        (status = 200, description = "Current and longest present streaks", body = StudentStreak),
+        (status = 404, description = "Student not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_student_streak(
+    path: web::Path<i32>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let student_id = path.into_inner();
+
+    let result = async {
+        let sessions = sqlx::query_as::<_, SessionKey>(
+            "SELECT DISTINCT date, session FROM active_attendance ORDER BY date, session",
+        )
+        .fetch_all(pool.get_ref())
+        .await?;
+        let records = sqlx::query_as::<_, (String, Option<String>, AttendanceStatus)>(
+            "SELECT date, session, status FROM active_attendance WHERE student_id = ? ORDER BY date",
+        )
+        .bind(student_id)
+        .fetch_all(pool.get_ref())
+        .await?;
+        Ok::<_, sqlx::Error>((sessions, records))
+    }
+    .await;
+
+    let (sessions, records) = result?;
+    if records.is_empty() {
+        return Err(AppError::NotFound(
+            "No attendance records for this student".to_string(),
+        ));
+    }
+    let records: HashMap<SessionKey, AttendanceStatus> = records
+        .into_iter()
+        .map(|(date, session, status)| ((date, session), status))
+        .collect();
+    let (current_streak, longest_streak) = present_streaks(&sessions, &records);
+    Ok(HttpResponse::Ok().json(StudentStreak {
+        current_streak,
+        longest_streak,
+    }))
+}
+
+// GET /students/birthdays-this-week
+// Lists students whose birthday (month and day) falls within the current ISO week.
+#[utoipa::path(
+    get,
+    path = "/v1/students/birthdays-this-week",
+    tag = "students",
+    summary = "This week's birthdays",
+    responses(
+        (status = 200, description = "Birthdays in the current week", body = Vec<Birthday>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_weekly_birthdays(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    let rows = sqlx::query_as::<_, (i32, String, String)>(
+        "SELECT id, name, birth_date FROM students WHERE birth_date IS NOT NULL",
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let today = today();
+    let monday = week_start(today);
+    let week: Vec<NaiveDate> = (0..7).map(|d| monday + chrono::Duration::days(d)).collect();
+
+    let mut birthdays: Vec<(NaiveDate, Birthday)> = Vec::new();
+    for (student_id, name, birth_date) in rows {
+        let Ok(born) = NaiveDate::parse_from_str(&birth_date, "%Y-%m-%d") else {
+            continue; // Ignore malformed birth dates rather than failing the whole list
+        };
+        // The week may span New Year, so check the birthday in each day's own year.
+        // Feb 29 birthdays are celebrated on Feb 28 in non-leap years.
+        let celebrated = week.iter().copied().find(|day| {
+            let birthday = NaiveDate::from_ymd_opt(day.year(), born.month(), born.day())
+                .or_else(|| NaiveDate::from_ymd_opt(day.year(), 2, 28));
+            birthday == Some(*day)
+        });
+        if let Some(day) = celebrated
+            && day.year() > born.year()
+        {
+            birthdays.push((
+                day,
+                Birthday {
+                    student_id,
+                    name,
+                    birth_date,
+                    age_turning: (day.year() - born.year()) as u32,
+                },
+            ));
+        }
+    }
+    birthdays.sort_by_key(|(day, b)| (*day, b.student_id));
+    let birthdays: Vec<Birthday> = birthdays.into_iter().map(|(_, b)| b).collect();
+    Ok(HttpResponse::Ok().json(birthdays))
+}
+
+// GET /report/data-entry-timeliness
+// Reports, per session date, how quickly attendance was entered after the fact.
+#[utoipa::path(
+    get,
+    path = "/v1/report/data-entry-timeliness",
+    tag = "reports",
+    summary = "Entry delays",
+    responses(
+        (status = 200, description = "Delay between a date and its entry", body = Vec<EntryTimeliness>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_entry_timeliness(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    // Records without created_at predate timestamp tracking and are left out.
+    let rows = sqlx::query_as::<_, (String, f64, i64)>(
+        "SELECT date, \
+                AVG(julianday(created_at) - julianday(date)) * 24.0, \
+                SUM(CASE WHEN julianday(created_at) - julianday(date) > 1.0 THEN 1 ELSE 0 END) \
+         FROM active_attendance WHERE created_at IS NOT NULL \
+         GROUP BY date ORDER BY date",
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let report: Vec<EntryTimeliness> = rows
+        .into_iter()
+        .map(|(date, avg_hours_to_entry, late_entries)| EntryTimeliness {
+            date,
+            avg_hours_to_entry,
+            late_entries,
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// GET /report/cohort-survival-curve
+// Follows the students who enrolled in `cohort_month` and reports, for each following month
+// up to the current one, the fraction of them that attended at least once.
+// A student's enrollment month is the month of their first attendance record.
+#[utoipa::path(
+    get,
+    path = "/v1/report/cohort-survival-curve",
+    tag = "reports",
+    summary = "Cohort retention",
+    params(SurvivalQuery),
+    responses(
+        (status = 200, description = "Share of the cohort still attending per month", body = Vec<SurvivalPoint>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_survival_curve(
+    query: web::Query<SurvivalQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let cohort_start = NaiveDate::parse_from_str(&format!("{}-01", query.cohort_month), "%Y-%m-%d")
+        .map_err(|_| {
+            AppError::BadRequest(format!(
+                "Invalid cohort_month '{}', expected YYYY-MM",
+                query.cohort_month
+            ))
+        })?;
+    let cohort_month = cohort_start.format("%Y-%m").to_string();
+
+    // Students whose first attendance record falls in the cohort month.
+    let cohort_sql = "SELECT student_id FROM active_attendance GROUP BY student_id \
+                      HAVING strftime('%Y-%m', MIN(date)) = ?";
+    let size_sql = format!("SELECT COUNT(*) FROM ({})", cohort_sql);
+    // Distinct (student, month) pairs in which a cohort member was present.
+    let attended_sql = format!(
+        "SELECT DISTINCT student_id, strftime('%Y-%m', date) FROM active_attendance \
+         WHERE status = 'Present' AND student_id IN ({})",
+        cohort_sql
+    );
+    let (cohort_size, attended) = tokio::join!(
+        sqlx::query_scalar::<_, i64>(&size_sql)
+            .bind(&cohort_month)
+            .fetch_one(pool.get_ref()),
+        sqlx::query_as::<_, (i32, String)>(&attended_sql)
+            .bind(&cohort_month)
+            .fetch_all(pool.get_ref()),
+    );
+
+    let cohort_size = cohort_size?;
+    let attended = attended?;
+    let month_index = |date: NaiveDate| date.year() * 12 + date.month0() as i32;
+    let start = month_index(cohort_start);
+    let months = (month_index(today()) - start).max(-1) + 1;
+    if cohort_size == 0 || months <= 0 {
+        return Ok(HttpResponse::Ok().json(Vec::<SurvivalPoint>::new()));
+    }
+
+    let mut active = vec![0i64; months as usize];
+    for (_, month) in attended {
+        let Ok(date) = NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d") else {
+            continue;
+        };
+        let offset = month_index(date) - start;
+        if (0..months).contains(&offset) {
+            active[offset as usize] += 1;
+        }
+    }
+
+    let curve: Vec<SurvivalPoint> = active
+        .into_iter()
+        .enumerate()
+        .map(|(offset, count)| SurvivalPoint {
+            months_since_enrollment: offset as u32,
+            retention: rate(count, cohort_size),
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(curve))
+}
+
+// GET /report/attendance-by-referral-source
+// Groups students by how they were referred and compares their average attendance.
+#[utoipa::path(
+    get,
+    path = "/v1/report/attendance-by-referral-source",
+    tag = "reports",
+    summary = "Rates by referral source",
+    responses(
+        (status = 200, description = "Present rate per referral source", body = Vec<ReferralAttendance>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_attendance_by_referral(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    // Average per-student rates so students with many records don't dominate their group.
+    let rows = sqlx::query_as::<_, (Option<String>, i64, f64)>(
+        "WITH per_student AS ( \
+             SELECT s.referral_source AS source, \
+                    AVG(CASE WHEN a.status = 'Present' THEN 1.0 ELSE 0.0 END) AS present_rate \
+             FROM active_attendance a JOIN students s ON s.id = a.student_id \
+             GROUP BY a.student_id \
+         ) \
+         SELECT source, COUNT(*), AVG(present_rate) FROM per_student \
+         GROUP BY source ORDER BY source",
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let report: Vec<ReferralAttendance> = rows
+        .into_iter()
+        .map(
+            |(source, student_count, avg_present_rate)| ReferralAttendance {
+                source,
+                student_count,
+                avg_present_rate,
+            },
+        )
+        .collect();
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// GET /students/{id}/contact-info
+// Returns the contact details on file for a student. Contact details are FERPA-protected, so
+// this needs an admin even when AUTH_PROTECT_READS leaves other reads open.
+#[utoipa::path(
+    get,
+    path = "/v1/students/{id}/contact-info",
+    tag = "students",
+    summary = "Contact details (admin only)",
+    responses(
+        (status = 200, description = "Contact details", body = ContactInfo),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Caller lacks the required role", body = ApiError),
+        (status = 404, description = "Contact info not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_contact_info(
+    _admin: RequireRole<AdminOnly>,
+    path: web::Path<i32>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let contact = sqlx::query_as::<_, ContactInfo>(
+        "SELECT parent_name, phone, email FROM contact_info WHERE student_id = ?",
+    )
+    .bind(path.into_inner())
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("No contact info for this student".to_string()))?;
+    Ok(HttpResponse::Ok().json(contact))
+}
+
+// PUT /students/{id}/contact-info
+// Creates or replaces the contact details for an existing student. Admins only, like GET.
+#[utoipa::path(
+    put,
+    path = "/v1/students/{id}/contact-info",
+    tag = "students",
+    summary = "Contact details (admin only)",
+    responses(
+        (status = 200, description = "Contact details as saved", body = ContactInfo),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Caller lacks the required role", body = ApiError),
+        (status = 404, description = "Student not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn put_contact_info(
+    RequireRole(auth, _): RequireRole<AdminOnly>,
+    path: web::Path<i32>,
+    data: web::Json<ContactInfo>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let student_id = path.into_inner();
+
+    let result = async {
+        let mut tx = pool.begin().await?;
+        let exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM students WHERE id = ?")
+            .bind(student_id)
+            .fetch_one(&mut *tx)
+            .await?
+            > 0;
+        if exists {
+            let old = sqlx::query_as::<_, ContactInfo>(
+                "SELECT parent_name, phone, email FROM contact_info WHERE student_id = ?",
+            )
+            .bind(student_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+            sqlx::query(
+                "INSERT INTO contact_info (student_id, parent_name, phone, email) \
+                 VALUES (?, ?, ?, ?) \
+                 ON CONFLICT(student_id) DO UPDATE SET \
+                     parent_name = excluded.parent_name, \
+                     phone = excluded.phone, \
+                     email = excluded.email",
+            )
+            .bind(student_id)
+            .bind(&data.parent_name)
+            .bind(&data.phone)
+            .bind(&data.email)
+            .execute(&mut *tx)
+            .await?;
+            // contact_info is keyed by student, so the student ID identifies the audited row.
+            record_audit(
+                &mut *tx,
+                "contact_info",
+                student_id as i64,
+                if old.is_some() { "UPDATE" } else { "INSERT" },
+                old.as_ref().and_then(|old| serde_json::to_string(old).ok()),
+                serde_json::to_string(&*data).ok(),
+                Some(&auth.subject),
+            )
+            .await?;
+            tx.commit().await?;
+        }
+        Ok::<bool, sqlx::Error>(exists)
+    }
+    .await;
+
+    if !result? {
+        return Err(AppError::NotFound("Student not found".to_string()));
+    }
+    tracing::info!(student_id, "contact info saved");
+    Ok(HttpResponse::Ok().json(data.into_inner()))
+}
+
+// GET /report/program-metrics-dashboard
+// Returns every key program metric in a single DashboardMetrics object.
+#[utoipa::path(
+    get,
+    path = "/v1/report/program-metrics-dashboard",
+    tag = "reports",
+    summary = "Executive KPIs",
+    responses(
+        (status = 200, description = "Program KPIs", body = DashboardMetrics),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_dashboard_metrics(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    let metrics = fetch_dashboard_metrics(pool.get_ref()).await?;
+    Ok(HttpResponse::Ok().json(metrics))
+}
+
+// POST /students/{id}/interventions
+// Logs a new intervention for an existing student and returns the stored record.
+#[utoipa::path(
+    post,
+    path = "/v1/students/{id}/interventions",
+    tag = "students",
+    summary = "New intervention",
+    responses(
+        (status = 201, description = "Intervention logged", body = Intervention),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 422, description = "Validation failed", body = ApiError),
+        (status = 404, description = "Student not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn add_intervention(
+    auth: AuthGuard,
+    path: web::Path<i32>,
+    data: web::Json<NewIntervention>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let student_id = path.into_inner();
+    if data.intervention_type.trim().is_empty() {
+        return Err(AppError::ValidationError(
+            "intervention_type must not be empty".to_string(),
+        ));
+    }
+
+    let result = async {
+        let mut tx = pool.begin().await?;
+        let exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM students WHERE id = ?")
+            .bind(student_id)
+            .fetch_one(&mut *tx)
+            .await?
+            > 0;
+        if !exists {
+            return Ok(None);
+        }
+        let intervention = sqlx::query_as::<_, Intervention>(
+            "INSERT INTO interventions (student_id, intervention_type, notes, outcome, staff_id) \
+             VALUES (?, ?, ?, ?, ?) RETURNING *",
+        )
+        .bind(student_id)
+        .bind(&data.intervention_type)
+        .bind(&data.notes)
+        .bind(&data.outcome)
+        .bind(&data.staff_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        record_audit(
+            &mut *tx,
+            "interventions",
+            intervention.id,
+            "INSERT",
+            None,
+            serde_json::to_string(&intervention).ok(),
+            Some(&auth.subject),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Some(intervention))
+    }
+    .await;
+
+    let intervention =
+        result?.ok_or_else(|| AppError::NotFound("Student not found".to_string()))?;
+    tracing::info!(
+        student_id,
+        intervention_id = intervention.id,
+        "intervention recorded"
+    );
+    Ok(HttpResponse::Created().json(intervention))
+}
+
+// GET /students/{id}/interventions (also served at /students/{id}/intervention-history)
+// Lists a student's interventions, most recent first.
+#[utoipa::path(
+    get,
+    path = "/v1/students/{id}/interventions",
+    tag = "students",
+    summary = "Interventions",
+    responses(
+        (status = 200, description = "Interventions, newest first", body = Vec<Intervention>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_interventions(
+    path: web::Path<i32>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let interventions = sqlx::query_as::<_, Intervention>(
+        "SELECT * FROM interventions WHERE student_id = ? ORDER BY created_at DESC, id DESC",
+    )
+    .bind(path.into_inner())
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(interventions))
+}
+
+// GET /report/attendance-audit-trail
+// Returns every logged change to the attendance record for (student_id, date), oldest first.
+#[utoipa::path(
+    get,
+    path = "/v1/report/attendance-audit-trail",
+    tag = "reports",
+    summary = "Record history",
+    params(AuditTrailQuery),
+    responses(
+        (status = 200, description = "Status changes of one record, oldest first", body = Vec<AuditEntry>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_record_audit_trail(
+    query: web::Query<AuditTrailQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let date = parse_date_param(&query.date)?;
+
+    // Match on either snapshot so deletes (no new_value) are found as well as inserts.
+    let entries = sqlx::query_as::<_, AuditEntry>(
+        "SELECT performed_at AS changed_at, \
+                COALESCE(performed_by, 'unknown') AS changed_by, \
+                json_extract(old_value, '$.status') AS old_status, \
+                json_extract(new_value, '$.status') AS new_status, \
+                action AS operation \
+         FROM audit_log \
+         WHERE table_name = 'attendance' \
+           AND ((json_extract(new_value, '$.student_id') = ?1 \
+                 AND json_extract(new_value, '$.date') = ?2) \
+             OR (json_extract(old_value, '$.student_id') = ?1 \
+                 AND json_extract(old_value, '$.date') = ?2)) \
+         ORDER BY performed_at, id",
+    )
+    .bind(query.student_id)
+    .bind(&date)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+// GET /report/attendance-recovery
+// Splits the last `look_back_weeks` weeks in half and lists students whose present rate rose
+// by at least 15 percentage points from the first half to the second.
+#[utoipa::path(
+    get,
+    path = "/v1/report/attendance-recovery",
+    tag = "reports",
+    summary = "Improving students",
+    params(RecoveryQuery),
+    responses(
+        (status = 200, description = "Students whose rate improved", body = Vec<RecoveryStory>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_recovery_stories(
+    query: web::Query<RecoveryQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let weeks = query.look_back_weeks.unwrap_or(6);
+    if weeks == 0 {
+        return Err(AppError::BadRequest(
+            "look_back_weeks must be at least 1".to_string(),
+        ));
+    }
+    let today = today();
+    let window_days = weeks as i64 * 7;
+    let start = today - chrono::Duration::days(window_days - 1);
+    let midpoint = start + chrono::Duration::days(window_days / 2);
+
+    let rows = sqlx::query_as::<_, (i32, Option<String>, i64, i64, i64, i64)>(
+        "SELECT a.student_id, s.name, \
+                SUM(CASE WHEN a.date < ?1 AND a.status = 'Present' THEN 1 ELSE 0 END), \
+                SUM(CASE WHEN a.date < ?1 THEN 1 ELSE 0 END), \
+                SUM(CASE WHEN a.date >= ?1 AND a.status = 'Present' THEN 1 ELSE 0 END), \
+                SUM(CASE WHEN a.date >= ?1 THEN 1 ELSE 0 END) \
+         FROM active_attendance a LEFT JOIN students s ON s.id = a.student_id \
+         WHERE a.date BETWEEN ?2 AND ?3 \
+         GROUP BY a.student_id",
+    )
+    .bind(midpoint.format("%Y-%m-%d").to_string())
+    .bind(start.format("%Y-%m-%d").to_string())
+    .bind(today.format("%Y-%m-%d").to_string())
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let mut stories: Vec<RecoveryStory> = rows
+        .into_iter()
+        // Both halves need data for a before/after comparison to mean anything.
+        .filter(|row| row.3 > 0 && row.5 > 0)
+        .map(
+            |(student_id, name, earlier_present, earlier_total, recent_present, recent_total)| {
+                let earlier_rate = rate(earlier_present, earlier_total);
+                let recent_rate = rate(recent_present, recent_total);
+                RecoveryStory {
+                    student_id,
+                    name,
+                    earlier_rate,
+                    recent_rate,
+                    improvement: recent_rate - earlier_rate,
+                }
+            },
+        )
+        .filter(|story| story.improvement >= RECOVERY_THRESHOLD)
+        .collect();
+    stories.sort_by(|a, b| b.improvement.total_cmp(&a.improvement));
+    Ok(HttpResponse::Ok().json(stories))
+}
+
+// GET /report/group-comparison-over-time
+// Returns weekly present rates per group as a time series for multi-line charts.
+#[utoipa::path(
+    get,
+    path = "/v1/report/group-comparison-over-time",
+    tag = "reports",
+    summary = "Weekly rates per group",
+    params(GroupComparisonQuery, DateRangeQuery),
+    responses(
+        (status = 200, description = "Weekly present rate per group", body = Vec<GroupWeek>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_group_comparison_time_series(
+    query: web::Query<GroupComparisonQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let (from, to) = query.range.bounds().map_err(AppError::BadRequest)?;
+    let group_ids: Option<Vec<i32>> = match &query.group_ids {
+        Some(ids) => Some(
+            ids.split(',')
+                .map(|id| id.trim().parse::<i32>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| {
+                    AppError::BadRequest(
+                        "group_ids must be a comma-separated list of integers".to_string(),
+                    )
+                })?,
+        ),
+        None => None,
+    };
+
+    // Daily totals per group; weeks are assembled in Rust using chrono's ISO weeks.
+    let rows = sqlx::query_as::<_, (String, i32, String, i64, i64)>(
+        "SELECT a.date, s.group_id, COALESCE(g.name, 'Group ' || s.group_id), \
+                SUM(CASE WHEN a.status = 'Present' THEN 1 ELSE 0 END), COUNT(*) \
+         FROM active_attendance a \
+         JOIN students s ON s.id = a.student_id \
+         LEFT JOIN groups g ON g.id = s.group_id \
+         WHERE s.group_id IS NOT NULL AND a.date BETWEEN ? AND ? \
+         GROUP BY a.date, s.group_id",
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    // week -> group_id -> (group_name, present, total)
+    let mut weeks: BTreeMap<String, BTreeMap<i32, (String, i64, i64)>> = BTreeMap::new();
+    for (date, group_id, group_name, present, total) in rows {
+        if group_ids
+            .as_ref()
+            .is_some_and(|ids| !ids.contains(&group_id))
+        {
+            continue;
+        }
+        let Ok(day) = NaiveDate::parse_from_str(&date, "%Y-%m-%d") else {
+            continue;
+        };
+        let iso = day.iso_week();
+        let entry = weeks
+            .entry(format!("{}-W{:02}", iso.year(), iso.week()))
+            .or_default()
+            .entry(group_id)
+            .or_insert((group_name, 0, 0));
+        entry.1 += present;
+        entry.2 += total;
+    }
+
+    let series: Vec<GroupWeek> = weeks
+        .into_iter()
+        .map(|(week, groups)| GroupWeek {
+            week,
+            groups: groups
+                .into_iter()
+                .map(|(group_id, (group_name, present, total))| GroupRate {
+                    group_id,
+                    group_name,
+                    rate: rate(present, total),
+                })
+                .collect(),
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(series))
+}
+
+// POST /report/export-scheduled
+// Registers a recurring export driven by a cron expression. Each run is written to EXPORT_DIR and
+// emailed to `recipient` through SMTP_HOST.
+#[utoipa::path(
+    post,
+    path = "/v1/report/export-scheduled",
+    tag = "exports",
+    summary = "Recurring export",
+    responses(
+        (status = 201, description = "Schedule created", body = ScheduledExport),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 422, description = "Validation failed", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn schedule_export(
+    auth: AuthGuard,
+    data: web::Json<NewScheduledExport>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    if let Err(e) = Cron::from_str(&data.cron) {
+        return Err(AppError::ValidationError(format!(
+            "Invalid cron expression: {}",
+            e
+        )));
+    }
+    if data.format != "csv" {
+        return Err(AppError::ValidationError(
+            "Unsupported format, expected \"csv\"".to_string(),
+        ));
+    }
+    if data.recipient.parse::<Mailbox>().is_err() {
+        return Err(AppError::ValidationError(format!(
+            "recipient must be an email address, got '{}'",
+            data.recipient
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+    let schedule = sqlx::query_as::<_, ScheduledExport>(
+        "INSERT INTO scheduled_exports (cron, format, recipient) VALUES (?, ?, ?) RETURNING *",
+    )
+    .bind(&data.cron)
+    .bind(&data.format)
+    .bind(&data.recipient)
+    .fetch_one(&mut *tx)
+    .await?;
+    record_audit(
+        &mut *tx,
+        "scheduled_exports",
+        schedule.id,
+        "INSERT",
+        None,
+        serde_json::to_string(&schedule).ok(),
+        Some(&auth.subject),
+    )
+    .await?;
+    tx.commit().await?;
+    tracing::info!(schedule_id = schedule.id, "export scheduled");
+    Ok(HttpResponse::Created().json(schedule))
+}
+
+// GET /report/attendance-improvement-over-enrollment
+// Computes each student's present rate in consecutive two-week windows starting from their
+// first attendance record, plus the average rate across students for each window.
+#[utoipa::path(
+    get,
+    path = "/v1/report/attendance-improvement-over-enrollment",
+    tag = "reports",
+    summary = "Student trajectories",
+    responses(
+        (status = 200, description = "Rate over time since enrollment", body = ImprovementTrajectories),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_improvement_trajectories(
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let rows = sqlx::query_as::<_, (i32, String, String)>(
+        "SELECT student_id, date, status FROM active_attendance ORDER BY student_id, date",
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    // student_id -> (enrollment date, window -> counts)
+    let mut windows: BTreeMap<i32, (NaiveDate, BTreeMap<u32, PresentCounts>)> = BTreeMap::new();
+    for (student_id, date, status) in rows {
+        let Ok(day) = NaiveDate::parse_from_str(&date, "%Y-%m-%d") else {
+            continue;
+        };
+        // Rows are sorted by date, so the first row seen is the enrollment date.
+        let (enrolled, counts) = windows
+            .entry(student_id)
+            .or_insert_with(|| (day, BTreeMap::new()));
+        let window = ((day - *enrolled).num_days() / TRAJECTORY_WINDOW_DAYS) as u32;
+        let entry = counts.entry(window).or_insert((0, 0));
+        if status == "Present" {
+            entry.0 += 1;
+        }
+        entry.1 += 1;
+    }
+
+    // window -> (sum of student rates, number of students)
+    let mut totals: BTreeMap<u32, (f64, usize)> = BTreeMap::new();
+    let students: Vec<StudentTrajectory> = windows
+        .into_iter()
+        .map(|(student_id, (_, counts))| {
+            let trajectory: Vec<TrajectoryPoint> = counts
+                .into_iter()
+                .map(|(window, (present, total))| TrajectoryPoint {
+                    window,
+                    rate: rate(present, total),
+                })
+                .collect();
+            for point in &trajectory {
+                let entry = totals.entry(point.window).or_insert((0.0, 0));
+                entry.0 += point.rate;
+                entry.1 += 1;
+            }
+            StudentTrajectory {
+                student_id,
+                trajectory,
+            }
+        })
+        .collect();
+    let average: Vec<TrajectoryPoint> = totals
+        .into_iter()
+        .map(|(window, (sum, count))| TrajectoryPoint {
+            window,
+            rate: sum / count as f64,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(ImprovementTrajectories { students, average }))
+}
+
+// GET /students/export-all-summaries
+// Exports one CSV row per student with their attendance totals, optionally limited to a
+// `from`/`to` date range.
+#[utoipa::path(
+    get,
+    path = "/v1/students/export-all-summaries",
+    tag = "exports",
+    summary = "Per-student CSV",
+    params(DateRangeQuery),
+    responses(
+        (status = 200, description = "Per-student totals as CSV", body = String, content_type = "text/csv"),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn export_all_summaries_csv(
+    query: web::Query<DateRangeQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let (from, to) = query.bounds().map_err(AppError::BadRequest)?;
+
+    // The date filter sits in the JOIN so students without records still get a row.
+    let rows = sqlx::query_as::<_, (i32, String, Option<String>, Option<String>, i64, i64, i64)>(
+        "SELECT s.id, s.name, s.grade, COALESCE(g.name, CAST(s.group_id AS TEXT)), \
+                COUNT(a.id), \
+                COALESCE(SUM(CASE WHEN a.status = 'Present' THEN 1 ELSE 0 END), 0), \
+                COALESCE(SUM(CASE WHEN a.status = 'Absent' THEN 1 ELSE 0 END), 0) \
+         FROM students s \
+         LEFT JOIN groups g ON g.id = s.group_id \
+         LEFT JOIN active_attendance a ON a.student_id = s.id AND a.date BETWEEN ? AND ? \
+         GROUP BY s.id ORDER BY s.name, s.id",
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let csv = (|| {
+        let mut wtr = Writer::from_writer(vec![]);
+        wtr.write_record([
+            "Student ID",
+            "Name",
+            "Grade",
+            "Group",
+            "Total Sessions",
+            "Present",
+            "Absent",
+            "Rate",
+        ])?;
+        for (id, name, grade, group, total, present, absent) in rows {
+            wtr.write_record([
+                id.to_string(),
+                name,
+                grade.unwrap_or_default(),
+                group.unwrap_or_default(),
+                total.to_string(),
+                present.to_string(),
+                absent.to_string(),
+                format!("{:.4}", rate(present, total)),
+            ])?;
+        }
+        wtr.into_inner()
+            .map_err(|e| csv::Error::from(e.into_error()))
+    })()?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv")
+        .insert_header((
+            "Content-Disposition",
+            "attachment; filename=\"student_summaries.csv\"",
+        ))
+        .body(csv))
+}
+
+// GET /report/consecutive-days-data-entry
+// Walks backward from today over program days (Monday to Friday) and counts how many in a row
+// had at least one attendance record created. Today only counts once something is entered,
+// since the day isn't over yet.
+#[utoipa::path(
+    get,
+    path = "/v1/report/consecutive-days-data-entry",
+    tag = "reports",
+    summary = "Data-entry streak",
+    responses(
+        (status = 200, description = "Consecutive program days with entries", body = DataEntryStreak),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_data_entry_streaks(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    let days = sqlx::query_scalar::<_, String>(
+        "SELECT DISTINCT date(created_at, 'localtime') FROM active_attendance \
+         WHERE created_at IS NOT NULL ORDER BY 1 DESC",
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let last_entry_date = days.first().cloned();
+    let entered: HashSet<NaiveDate> = days
+        .iter()
+        .filter_map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+        .collect();
+
+    let mut current_streak_days = 0;
+    let mut first_gap_date = None;
+    if !entered.is_empty() {
+        let today = today();
+        let mut cursor = if is_program_day(today) && entered.contains(&today) {
+            today
+        } else {
+            previous_program_day(today)
+        };
+        // Every entry date is finite, so the walk always reaches a gap.
+        while entered.contains(&cursor) {
+            current_streak_days += 1;
+            cursor = previous_program_day(cursor);
+        }
+        first_gap_date = Some(cursor.format("%Y-%m-%d").to_string());
+    }
+
+    Ok(HttpResponse::Ok().json(DataEntryStreak {
+        current_streak_days,
+        last_entry_date,
+        first_gap_date,
+    }))
+}
+
+// GET /report/attendance-parity
+// Compares attendance rates across subgroups of students as a simple equity indicator.
+#[utoipa::path(
+    get,
+    path = "/v1/report/attendance-parity",
+    tag = "reports",
+    summary = "Subgroup parity",
+    params(ParityQuery),
+    responses(
+        (status = 200, description = "Present rate per subgroup", body = ParityReport),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_attendance_parity(
+    query: web::Query<ParityQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    // The column is interpolated into SQL, so only allowlisted names are accepted.
+    let column = match query.group_by.as_str() {
+        "grade" => "s.grade",
+        "group_id" => "s.group_id",
+        "referral_source" => "s.referral_source",
+        "address_zip" => "s.address_zip",
+        other => {
+            return Err(AppError::BadRequest(format!(
+                "Unsupported group_by '{}', expected grade, group_id, referral_source or address_zip",
+                other
+            )));
+        }
+    };
+
+    let sql = format!(
+        "SELECT COALESCE(CAST({col} AS TEXT), 'unknown'), \
+                AVG(CASE WHEN a.status = 'Present' THEN 1.0 ELSE 0.0 END), \
+                COUNT(DISTINCT a.student_id) \
+         FROM active_attendance a JOIN students s ON s.id = a.student_id \
+         GROUP BY 1 ORDER BY 1",
+        col = column
+    );
+    let rows = sqlx::query_as::<_, (String, f64, i64)>(&sql)
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let subgroups: Vec<ParitySubgroup> = rows
+        .into_iter()
+        .map(|(subgroup, rate, student_count)| ParitySubgroup {
+            subgroup,
+            rate,
+            student_count,
+        })
+        .collect();
+    let max = subgroups.iter().map(|g| g.rate).fold(f64::MIN, f64::max);
+    let min = subgroups.iter().map(|g| g.rate).fold(f64::MAX, f64::min);
+    Ok(HttpResponse::Ok().json(ParityReport {
+        group_by: query.group_by.clone(),
+        max_rate_gap: if subgroups.is_empty() { 0.0 } else { max - min },
+        subgroups,
+    }))
+}
+
+// GET /report/attendance-by-staff-day-of-week
+// Counts records per staff member and session weekday to reveal coverage gaps.
+#[utoipa::path(
+    get,
+    path = "/v1/report/attendance-by-staff-day-of-week",
+    tag = "reports",
+    summary = "Staff coverage by weekday",
+    responses(
+        (status = 200, description = "Records per staff member and weekday", body = Vec<StaffDayPattern>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_staff_day_patterns(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    let rows = sqlx::query_as::<_, (String, Option<i64>, i64)>(
+        "SELECT recorded_by, CAST(strftime('%w', date) AS INTEGER), COUNT(*) \
+         FROM active_attendance WHERE recorded_by IS NOT NULL \
+         GROUP BY 1, 2 ORDER BY 1, 2",
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let patterns: Vec<StaffDayPattern> = rows
+        .into_iter()
+        .filter_map(|(staff_id, weekday, records)| {
+            // Malformed dates have no weekday and are skipped.
+            let day_of_week = WEEKDAY_NAMES.get(weekday? as usize)?.to_string();
+            Some(StaffDayPattern {
+                staff_id,
+                day_of_week,
+                records,
+            })
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(patterns))
+}
+
+// GET /report/attendance-recidivism
+// Finds every time a student came back with a "Present" record after going 30 or more days
+// without one.
+#[utoipa::path(
+    get,
+    path = "/v1/report/attendance-recidivism",
+    tag = "reports",
+    summary = "Re-enrollment events",
+    responses(
+        (status = 200, description = "Students who returned after a long gap", body = Vec<ReenrollmentEvent>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_recidivism_report(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    let rows = sqlx::query_as::<_, (i32, Option<String>, String)>(
+        "SELECT a.student_id, s.name, a.date \
+         FROM active_attendance a LEFT JOIN students s ON s.id = a.student_id \
+         WHERE a.status = 'Present' \
+         ORDER BY a.student_id, a.date",
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let mut events: Vec<ReenrollmentEvent> = Vec::new();
+    let mut previous: Option<(i32, NaiveDate, String)> = None;
+    for (student_id, name, date) in rows {
+        let Ok(day) = NaiveDate::parse_from_str(&date, "%Y-%m-%d") else {
+            continue;
+        };
+        if let Some((prev_id, prev_day, prev_date)) = &previous
+            && *prev_id == student_id
+        {
+            let gap_days = (day - *prev_day).num_days();
+            if gap_days >= DROPOUT_GAP_DAYS {
+                events.push(ReenrollmentEvent {
+                    student_id,
+                    name,
+                    gap_start: prev_date.clone(),
+                    gap_end: date.clone(),
+                    gap_days,
+                });
+            }
+        }
+        previous = Some((student_id, day, date));
+    }
+
+    Ok(HttpResponse::Ok().json(events))
+}
+
+// GET /report/peer-influence
+// Returns the 10 student pairs whose absence days overlap the most.
+#[utoipa::path(
+    get,
+    path = "/v1/report/peer-influence",
+    tag = "reports",
+    summary = "Co-absent student pairs",
+    responses(
+        (status = 200, description = "Pairs of students often absent together", body = Vec<PeerPair>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_peer_influence(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    // Self-join absences on date to find shared days; the union size for the Jaccard
+    // denominator is |A| + |B| - |A ∩ B|.
+    let pairs = sqlx::query_as::<_, PeerPair>(
+        "WITH absences AS ( \
+             SELECT DISTINCT student_id, date FROM active_attendance WHERE status = 'Absent' \
+         ), \
+         totals AS ( \
+             SELECT student_id, COUNT(*) AS absent_days FROM absences GROUP BY student_id \
+         ) \
+         SELECT a.student_id AS student_a, b.student_id AS student_b, \
+                CAST(COUNT(*) AS REAL) / (ta.absent_days + tb.absent_days - COUNT(*)) \
+                    AS co_absence_rate, \
+                COUNT(*) AS common_absences \
+         FROM absences a \
+         JOIN absences b ON b.date = a.date AND a.student_id < b.student_id \
+         JOIN totals ta ON ta.student_id = a.student_id \
+         JOIN totals tb ON tb.student_id = b.student_id \
+         GROUP BY a.student_id, b.student_id \
+         ORDER BY co_absence_rate DESC, common_absences DESC, student_a, student_b \
+         LIMIT 10",
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(pairs))
+}
+
+// GET /report/optimal-session-day
+// Ranks weekdays by their average daily present rate to inform scheduling decisions.
+#[utoipa::path(
+    get,
+    path = "/v1/report/optimal-session-day",
+    tag = "reports",
+    summary = "Best weekday",
+    responses(
+        (status = 200, description = "Weekday with the best turnout", body = OptimalSessionDay),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_optimal_session_day(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    // Rate per session date first, so busy days don't outweigh quiet ones.
+    let rows = sqlx::query_as::<_, (i64, f64, i64)>(
+        "WITH daily AS ( \
+             SELECT date, AVG(CASE WHEN status = 'Present' THEN 1.0 ELSE 0.0 END) AS present_rate \
+             FROM active_attendance GROUP BY date \
+         ) \
+         SELECT CAST(strftime('%w', date) AS INTEGER) AS weekday, AVG(present_rate), COUNT(*) \
+         FROM daily WHERE strftime('%w', date) IS NOT NULL \
+         GROUP BY weekday ORDER BY 2 DESC, weekday",
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let weekdays: Vec<WeekdayRate> = rows
+        .into_iter()
+        .filter_map(|(weekday, avg_rate, sample_size)| {
+            Some(WeekdayRate {
+                weekday: WEEKDAY_NAMES.get(weekday as usize)?.to_string(),
+                avg_rate,
+                sample_size,
+            })
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(OptimalSessionDay {
+        recommended_day: weekdays.first().map(|w| w.weekday.clone()),
+        weekdays,
+    }))
+}
+
+// GET /students/{id}/incentive-eligibility
+// Reports the student's current incentive tier and progress toward the next one.
+#[utoipa::path(
+    get,
+    path = "/v1/students/{id}/incentive-eligibility",
+    tag = "students",
+    summary = "Incentive tier",
+    responses(
+        (status = 200, description = "Current and next incentive tier", body = IncentiveEligibility),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_incentive_eligibility(
+    path: web::Path<i32>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let total = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM active_attendance WHERE student_id = ? AND status = 'Present'",
+    )
+    .bind(path.into_inner())
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let current = INCENTIVE_TIERS
+        .iter()
+        .rev()
+        .find(|(_, needed)| total >= *needed);
+    let next = INCENTIVE_TIERS.iter().find(|(_, needed)| total < *needed);
+    Ok(HttpResponse::Ok().json(IncentiveEligibility {
+        current_tier: current.map(|(tier, _)| tier.to_string()),
+        sessions_toward_next_tier: next.map_or(0, |(_, needed)| (needed - total) as i32),
+        next_tier: next.map(|(tier, _)| tier.to_string()),
+        total_present: total as i32,
+    }))
+}
+
+// GET /report/all-students-monthly-grid
+// Pivots a year of attendance into one row per student and one column per month.
+#[utoipa::path(
+    get,
+    path = "/v1/report/all-students-monthly-grid",
+    tag = "reports",
+    summary = "Students x months grid",
+    params(MonthlyGridQuery),
+    responses(
+        (status = 200, description = "Present rate per student and month", body = MonthlyGrid),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_monthly_grid(
+    query: web::Query<MonthlyGridQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let year = query.year;
+    if !(1000..=9999).contains(&year) {
+        return Err(AppError::BadRequest(
+            "year must be a four-digit year".to_string(),
+        ));
+    }
+
+    // One CASE WHEN column per month; NULLIF turns months without records into NULL.
+    let columns: Vec<String> = (1..=12)
+        .map(|month| {
+            format!(
+                "CAST(SUM(CASE WHEN strftime('%m', a.date) = '{m:02}' AND a.status = 'Present' \
+                 THEN 1 ELSE 0 END) AS REAL) \
+                 / NULLIF(SUM(CASE WHEN strftime('%m', a.date) = '{m:02}' THEN 1 ELSE 0 END), 0)",
+                m = month
+            )
+        })
+        .collect();
+    let sql = format!(
+        "SELECT a.student_id, COALESCE(s.name, 'Student ' || a.student_id), {} \
+         FROM active_attendance a LEFT JOIN students s ON s.id = a.student_id \
+         WHERE strftime('%Y', a.date) = ? \
+         GROUP BY a.student_id ORDER BY a.student_id",
+        columns.join(", ")
+    );
+
+    let rows = sqlx::query(&sql)
+        .bind(format!("{:04}", year))
+        .fetch_all(pool.get_ref())
+        .await;
+
+    let grid = rows.and_then(|rows| {
+        rows.iter()
+            .map(|row| {
+                Ok(MonthlyGridRow {
+                    student_id: row.try_get(0)?,
+                    name: row.try_get(1)?,
+                    monthly_rates: (2..14)
+                        .map(|i| row.try_get::<Option<f64>, _>(i))
+                        .collect::<Result<_, _>>()?,
+                })
+            })
+            .collect::<Result<Vec<_>, sqlx::Error>>()
+    });
+
+    let students = grid?;
+    Ok(HttpResponse::Ok().json(MonthlyGrid {
+        months: (1..=12).map(|m| format!("{:04}-{:02}", year, m)).collect(),
+        students,
+    }))
+}
+
+// GET /report/attendance-trend-by-grade
+// Fits a regression line to each grade's weekly present rate over the past 12 weeks.
+#[utoipa::path(
+    get,
+    path = "/v1/report/attendance-trend-by-grade",
+    tag = "reports",
+    summary = "Weekly rate trend per grade",
+    responses(
+        (status = 200, description = "Weekly rate trend per grade", body = Vec<GradeTrend>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_trend_by_grade(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    let first_week = week_start(today()) - chrono::Duration::weeks(TREND_WEEKS - 1);
+
+    let rows = sqlx::query_as::<_, (String, String, i64, i64)>(
+        "SELECT COALESCE(s.grade, 'unknown'), a.date, \
+                SUM(CASE WHEN a.status = 'Present' THEN 1 ELSE 0 END), COUNT(*) \
+         FROM active_attendance a JOIN students s ON s.id = a.student_id \
+         WHERE a.date >= ? \
+         GROUP BY 1, a.date",
+    )
+    .bind(first_week.format("%Y-%m-%d").to_string())
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    // grade -> week index -> (present, total)
+    let mut grades: BTreeMap<String, BTreeMap<i64, PresentCounts>> = BTreeMap::new();
+    for (grade, date, present, total) in rows {
+        let Ok(day) = NaiveDate::parse_from_str(&date, "%Y-%m-%d") else {
+            continue;
+        };
+        let week = (week_start(day) - first_week).num_weeks();
+        if week >= TREND_WEEKS {
+            continue;
+        }
+        let entry = grades.entry(grade).or_default().entry(week).or_default();
+        entry.0 += present;
+        entry.1 += total;
+    }
+
+    let trends: Vec<GradeTrend> = grades
+        .into_iter()
+        .map(|(grade, weeks)| {
+            // Weeks without records are left out rather than counted as zero.
+            let points: Vec<(f64, f64)> = weeks
+                .into_iter()
+                .map(|(week, (present, total))| (week as f64, rate(present, total)))
+                .collect();
+            let (slope, r_squared) = linear_regression(&points);
+            let significant = r_squared >= TREND_MIN_R_SQUARED;
+            let interpretation = if significant && slope >= TREND_SLOPE_THRESHOLD {
+                "improving"
+            } else if significant && slope <= -TREND_SLOPE_THRESHOLD {
+                "declining"
+            } else {
+                "stable"
+            };
+            GradeTrend {
+                grade,
+                slope,
+                r_squared,
+                interpretation: interpretation.to_string(),
+            }
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(trends))
+}
+
+// GET /report/students-at-risk-of-losing-benefits
+// Lists students below the funder's attendance threshold and how quickly they can recover.
+#[utoipa::path(
+    get,
+    path = "/v1/report/students-at-risk-of-losing-benefits",
+    tag = "reports",
+    summary = "Students below benefits threshold",
+    params(BenefitsRiskQuery),
+    responses(
+        (status = 200, description = "Students below the threshold", body = Vec<BenefitsRisk>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_benefits_at_risk(
+    query: web::Query<BenefitsRiskQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let threshold = query.threshold.unwrap_or(0.8);
+    let look_ahead = query.look_ahead_sessions.unwrap_or(3);
+    if !(threshold > 0.0 && threshold <= 1.0) {
+        return Err(AppError::BadRequest(
+            "threshold must be between 0 and 1".to_string(),
+        ));
+    }
+    if look_ahead < 0 {
+        return Err(AppError::BadRequest(
+            "look_ahead_sessions must not be negative".to_string(),
+        ));
+    }
+
+    let rows = sqlx::query_as::<_, (i32, Option<String>, i64, i64)>(
+        "SELECT a.student_id, s.name, \
+                SUM(CASE WHEN a.status = 'Present' THEN 1 ELSE 0 END), COUNT(*) \
+         FROM active_attendance a LEFT JOIN students s ON s.id = a.student_id \
+         GROUP BY a.student_id ORDER BY a.student_id",
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let at_risk: Vec<BenefitsRisk> = rows
+        .into_iter()
+        .filter(|(_, _, present, total)| rate(*present, *total) < threshold)
+        .map(|(student_id, name, present, total)| {
+            // Smallest k with (present + k) / (total + k) >= threshold.
+            let needed = if threshold < 1.0 {
+                ((threshold * total as f64 - present as f64) / (1.0 - threshold)).ceil() as i32
+            } else {
+                i32::MAX
+            };
+            BenefitsRisk {
+                student_id,
+                name,
+                rate: rate(present, total),
+                sessions_needed_to_recover: (needed <= look_ahead).then_some(needed),
+            }
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(at_risk))
+}
+
+// GET /report/two-week-absence-alert
+// Flags students not seen in 14 days, separating recorded absences from missing data entry.
+#[utoipa::path(
+    get,
+    path = "/v1/report/two-week-absence-alert",
+    tag = "reports",
+    summary = "Students unseen for two weeks",
+    responses(
+        (status = 200, description = "Students not seen in two weeks", body = Vec<TwoWeekAlert>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_two_week_alerts(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    let since = (today() - chrono::Duration::days(TWO_WEEK_ALERT_DAYS - 1))
+        .format("%Y-%m-%d")
+        .to_string();
+
+    // Roster is every known student, whether enrolled in `students` or only seen in attendance.
+    let rows = sqlx::query_as::<_, (i32, Option<String>, Option<String>, bool)>(
+        "WITH roster AS (SELECT id AS student_id FROM students \
+                         UNION SELECT student_id FROM active_attendance) \
+         SELECT r.student_id, s.name, \
+                (SELECT MAX(date) FROM active_attendance \
+                 WHERE student_id = r.student_id AND status = 'Present'), \
+                EXISTS (SELECT 1 FROM active_attendance WHERE student_id = r.student_id AND date >= ?1) \
+         FROM roster r LEFT JOIN students s ON s.id = r.student_id \
+         WHERE NOT EXISTS (SELECT 1 FROM active_attendance \
+                           WHERE student_id = r.student_id AND status = 'Present' AND date >= ?1) \
+         ORDER BY r.student_id",
+    )
+    .bind(since)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let alerts: Vec<TwoWeekAlert> = rows
+        .into_iter()
+        .map(
+            |(student_id, name, last_present, has_records)| TwoWeekAlert {
+                student_id,
+                name,
+                last_present,
+                reason: if has_records { "absent" } else { "no_record" }.to_string(),
+            },
+        )
+        .collect();
+    Ok(HttpResponse::Ok().json(alerts))
+}
+
+// GET /students/{id}/siblings
+// Lists the other students in the same family; empty when the student has no family_id.
+#[utoipa::path(
+    get,
+    path = "/v1/students/{id}/siblings",
+    tag = "students",
+    summary = "Students in the same family",
+    responses(
+        (status = 200, description = "Other students in the same family", body = Vec<Student>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_siblings(
+    path: web::Path<i32>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    let siblings = sqlx::query_as::<_, Student>(
+        "SELECT * FROM students \
+         WHERE family_id = (SELECT family_id FROM students WHERE id = ?) AND id != ? \
+         ORDER BY id",
+    )
+    .bind(id)
+    .bind(id)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(siblings))
+}
+
+// Rejects a group_id that names no group, so students are only ever assigned to real groups.
+async fn ensure_group_exists(pool: &SqlitePool, group_id: Option<i32>) -> Result<(), AppError> {
+    let Some(group_id) = group_id else {
+        return Ok(());
+    };
+    let exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM groups WHERE id = ?")
+        .bind(group_id)
+        .fetch_one(pool)
+        .await?
+        > 0;
+    if exists {
+        Ok(())
+    } else {
+        Err(AppError::ValidationError(format!(
+            "Group {} does not exist",
+            group_id
+        )))
+    }
+}
+
+// POST /students
+// Enrolls a new student and returns the stored row.
+#[utoipa::path(
+    post,
+    path = "/v1/students",
+    tag = "students",
+    summary = "New student",
+    responses(
+        (status = 201, description = "Student created", body = Student),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 422, description = "Validation failed", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn create_student(
+    auth: AuthGuard,
+    data: web::Json<NewStudent>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    if data.name.trim().is_empty() {
+        return Err(AppError::ValidationError(
+            "name must not be empty".to_string(),
+        ));
+    }
+    ensure_group_exists(pool.get_ref(), data.group_id).await?;
+
+    let mut tx = pool.begin().await?;
+    let student = sqlx::query_as::<_, Student>(
+        "INSERT INTO students \
+             (name, email, grade, group_id, address_zip, birth_date, referral_source, family_id) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING *",
+    )
+    .bind(&data.name)
+    .bind(&data.email)
+    .bind(&data.grade)
+    .bind(data.group_id)
+    .bind(&data.address_zip)
+    .bind(&data.birth_date)
+    .bind(&data.referral_source)
+    .bind(data.family_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    record_audit(
+        &mut *tx,
+        "students",
+        student.id as i64,
+        "INSERT",
+        None,
+        serde_json::to_string(&student).ok(),
+        Some(&auth.subject),
+    )
+    .await?;
+    tx.commit().await?;
+    tracing::info!(student_id = student.id, "student created");
+    Ok(HttpResponse::Created().json(student))
+}
+
+// GET /students
+// Lists every enrolled student ordered by id.
+#[utoipa::path(
+    get,
+    path = "/v1/students",
+    tag = "students",
+    summary = "All students",
+    responses(
+        (status = 200, description = "Every student", body = Vec<Student>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn list_students(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    let students = sqlx::query_as::<_, Student>("SELECT * FROM students ORDER BY id")
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(students))
+}
+
+// GET /students/{id}
+// Returns a single student with the number of siblings enrolled and of interventions logged.
+#[utoipa::path(
+    get,
+    path = "/v1/students/{id}",
+    tag = "students",
+    summary = "One student",
+    responses(
+        (status = 200, description = "The student", body = StudentDetail),
+        (status = 404, description = "Student not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_student(
+    path: web::Path<i32>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let student = sqlx::query_as::<_, Student>("SELECT * FROM students WHERE id = ?")
+        .bind(path.into_inner())
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Student not found".to_string()))?;
+    let sibling_count = match student.family_id {
+        Some(family_id) => {
+            sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM students WHERE family_id = ? AND id != ?",
+            )
+            .bind(family_id)
+            .bind(student.id)
+            .fetch_one(pool.get_ref())
+            .await?
+        }
+        None => 0,
+    };
+    let intervention_count =
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM interventions WHERE student_id = ?")
+            .bind(student.id)
+            .fetch_one(pool.get_ref())
+            .await?;
+    Ok(HttpResponse::Ok().json(StudentDetail {
+        student,
+        sibling_count,
+        intervention_count,
+    }))
+}
+
+// PUT /students/{id}
+// Replaces every field of an existing student.
+#[utoipa::path(
+    put,
+    path = "/v1/students/{id}",
+    tag = "students",
+    summary = "Student fields",
+    responses(
+        (status = 200, description = "The updated student", body = Student),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 422, description = "Validation failed", body = ApiError),
+        (status = 404, description = "Student not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn update_student(
+    auth: AuthGuard,
+    path: web::Path<i32>,
+    data: web::Json<NewStudent>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    if data.name.trim().is_empty() {
+        return Err(AppError::ValidationError(
+            "name must not be empty".to_string(),
+        ));
+    }
+    ensure_group_exists(pool.get_ref(), data.group_id).await?;
+    let id = path.into_inner();
+
+    let mut tx = pool.begin().await?;
+    let old = sqlx::query_as::<_, Student>("SELECT * FROM students WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Student not found".to_string()))?;
+    let student = sqlx::query_as::<_, Student>(
+        "UPDATE students SET name = ?, email = ?, grade = ?, group_id = ?, address_zip = ?, \
+             birth_date = ?, referral_source = ?, family_id = ? \
+         WHERE id = ? RETURNING *",
+    )
+    .bind(&data.name)
+    .bind(&data.email)
+    .bind(&data.grade)
+    .bind(data.group_id)
+    .bind(&data.address_zip)
+    .bind(&data.birth_date)
+    .bind(&data.referral_source)
+    .bind(data.family_id)
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await?;
+    record_audit(
+        &mut *tx,
+        "students",
+        id as i64,
+        "UPDATE",
+        serde_json::to_string(&old).ok(),
+        serde_json::to_string(&student).ok(),
+        Some(&auth.subject),
+    )
+    .await?;
+    tx.commit().await?;
+    tracing::info!(student_id = student.id, "student updated");
+    Ok(HttpResponse::Ok().json(student))
+}
+
+// DELETE /students/{id}
+// Removes a student. Students with attendance history are kept so reports stay intact.
+#[utoipa::path(
+    delete,
+    path = "/v1/students/{id}",
+    tag = "students",
+    summary = "Student without attendance",
+    responses(
+        (status = 204, description = "Student deleted"),
+        (status = 404, description = "Student not found", body = ApiError),
+        (status = 409, description = "Student has attendance records", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn delete_student(
+    auth: AuthGuard,
+    path: web::Path<i32>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    // Soft-deleted records still reference the student, so they count too.
+    let records =
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM attendance WHERE student_id = ?")
+            .bind(id)
+            .fetch_one(pool.get_ref())
+            .await?;
+
+    if records > 0 {
+        return Err(AppError::Conflict(format!(
+            "Student has {} attendance records and cannot be deleted",
+            records
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+    let deleted = sqlx::query_as::<_, Student>("DELETE FROM students WHERE id = ? RETURNING *")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Student not found".to_string()))?;
+    record_audit(
+        &mut *tx,
+        "students",
+        id as i64,
+        "DELETE",
+        serde_json::to_string(&deleted).ok(),
+        None,
+        Some(&auth.subject),
+    )
+    .await?;
+    tx.commit().await?;
+    tracing::info!(student_id = id, "student deleted");
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Loads a group or answers 404.
+async fn fetch_group(pool: &SqlitePool, id: i32) -> Result<Group, AppError> {
+    sqlx::query_as::<_, Group>("SELECT * FROM groups WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Group not found".to_string()))
+}
+
+// GET /groups
+// Lists every group ordered by id.
+#[utoipa::path(
+    get,
+    path = "/v1/groups",
+    tag = "groups",
+    summary = "All groups",
+    responses(
+        (status = 200, description = "Every group", body = Vec<Group>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn list_groups(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    let groups = sqlx::query_as::<_, Group>("SELECT * FROM groups ORDER BY id")
+        .fetch_all(pool.get_ref())
+        .await?;
+    Ok(HttpResponse::Ok().json(groups))
+}
+
+// POST /groups
+// Creates a group that students can then be assigned to through their group_id.
+#[utoipa::path(
+    post,
+    path = "/v1/groups",
+    tag = "groups",
+    summary = "New group",
+    responses(
+        (status = 201, description = "Group created", body = Group),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 422, description = "Validation failed", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn create_group(
+    auth: AuthGuard,
+    data: web::Json<NewGroup>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let name = data.name.trim();
+    if name.is_empty() {
+        return Err(AppError::ValidationError(
+            "name must not be empty".to_string(),
+        ));
+    }
+
+    let mut tx = pool.begin().await?;
+    let group = sqlx::query_as::<_, Group>(
+        "INSERT INTO groups (name, description) VALUES (?, ?) RETURNING *",
+    )
+    .bind(name)
+    .bind(&data.description)
+    .fetch_one(&mut *tx)
+    .await?;
+    record_audit(
+        &mut *tx,
+        "groups",
+        group.id as i64,
+        "INSERT",
+        None,
+        serde_json::to_string(&group).ok(),
+        Some(&auth.subject),
+    )
+    .await?;
+    tx.commit().await?;
+    tracing::info!(group_id = group.id, "group created");
+    Ok(HttpResponse::Created().json(group))
+}
+
+// GET /groups/{id}/students
+// Lists the students assigned to a group, ordered by id.
+#[utoipa::path(
+    get,
+    path = "/v1/groups/{id}/students",
+    tag = "groups",
+    summary = "Students in a group",
+    responses(
+        (status = 200, description = "Students in the group", body = Vec<Student>),
+        (status = 404, description = "Group not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_group_students(
+    path: web::Path<i32>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let group = fetch_group(pool.get_ref(), path.into_inner()).await?;
+    let students =
+        sqlx::query_as::<_, Student>("SELECT * FROM students WHERE group_id = ? ORDER BY id")
+            .bind(group.id)
+            .fetch_all(pool.get_ref())
+            .await?;
+    Ok(HttpResponse::Ok().json(students))
+}
+
+// GET /groups/{id}/report
+// Summarizes attendance of the students currently in a group, optionally limited to
+// ?start=/?end=.
+#[utoipa::path(
+    get,
+    path = "/v1/groups/{id}/report",
+    tag = "groups",
+    summary = "A group's attendance summary",
+    params(DateRangeQuery),
+    responses(
+        (status = 200, description = "The group's totals for the range", body = GroupReport),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 404, description = "Group not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_group_report(
+    path: web::Path<i32>,
+    range: web::Query<DateRangeQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let (start, end) = range.bounds().map_err(AppError::BadRequest)?;
+    let group = fetch_group(pool.get_ref(), path.into_inner()).await?;
+
+    let student_count =
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM students WHERE group_id = ?")
+            .bind(group.id)
+            .fetch_one(pool.get_ref())
+            .await?;
+    let stats = sqlx::query_as::<_, PeriodStats>(
+        "SELECT COUNT(*) AS total_records, \
+                COALESCE(SUM(CASE WHEN a.status = 'Present' THEN 1 ELSE 0 END), 0) AS present_count, \
+                COALESCE(SUM(CASE WHEN a.status = 'Absent' THEN 1 ELSE 0 END), 0) AS absent_count, \
+                COALESCE(AVG(CASE WHEN a.status = 'Present' THEN 1.0 ELSE 0.0 END), 0.0) \
+                    AS attendance_rate \
+         FROM active_attendance a JOIN students s ON s.id = a.student_id \
+         WHERE s.group_id = ? AND a.date BETWEEN ? AND ?",
+    )
+    .bind(group.id)
+    .bind(&start)
+    .bind(&end)
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(GroupReport {
+        group_id: group.id,
+        name: group.name,
+        student_count,
+        stats,
+    }))
+}
+
+// GET /facilitators
+// Lists every facilitator ordered by id.
+#[utoipa::path(
+    get,
+    path = "/v1/facilitators",
+    tag = "facilitators",
+    summary = "All facilitators",
+    responses(
+        (status = 200, description = "Every facilitator", body = Vec<Facilitator>),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn list_facilitators(pool: web::Data<SqlitePool>) -> Result<HttpResponse, AppError> {
+    let facilitators = sqlx::query_as::<_, Facilitator>("SELECT * FROM facilitators ORDER BY id")
+        .fetch_all(pool.get_ref())
+        .await?;
+    Ok(HttpResponse::Ok().json(facilitators))
+}
+
+// GET /facilitators/{id}/records
+// Returns one page of the attendance records credited to a facilitator, ordered by date.
+#[utoipa::path(
+    get,
+    path = "/v1/facilitators/{id}/records",
+    tag = "facilitators",
+    summary = "Records credited to a facilitator",
+    params(PaginationQuery),
+    responses(
+        (status = 200, description = "One page of records credited to the facilitator", body = PaginatedResponse<Attendance>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 404, description = "Facilitator not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_facilitator_records(
+    path: web::Path<i64>,
+    pagination: web::Query<PaginationQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    let (page, per_page, offset) = pagination.resolve().map_err(AppError::BadRequest)?;
+
+    let exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM facilitators WHERE id = ?")
+        .bind(id)
+        .fetch_one(pool.get_ref())
+        .await?
+        > 0;
+    if !exists {
+        return Err(AppError::NotFound("Facilitator not found".to_string()));
+    }
+
+    let total = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM active_attendance WHERE facilitator_id = ?",
+    )
+    .bind(id)
+    .fetch_one(pool.get_ref())
+    .await?;
+    let data = sqlx::query_as::<_, Attendance>(
+        "SELECT * FROM active_attendance WHERE facilitator_id = ? ORDER BY date, id LIMIT ? OFFSET ?",
+    )
+    .bind(id)
+    .bind(per_page)
+    .bind(offset)
+    .fetch_all(pool.get_ref())
+    .await?;
+    Ok(HttpResponse::Ok().json(PaginatedResponse {
+        data,
+        page,
+        per_page,
+        total,
+    }))
+}
+
+// POST /auth/token
+// Exchanges a username and password for a signed JWT carrying the user's role.
+#[utoipa::path(
+    post,
+    path = "/v1/auth/token",
+    tag = "auth",
+    summary = "Credentials for a JWT",
+    responses(
+        (status = 200, description = "Signed bearer token", body = TokenResponse),
+        (status = 401, description = "Wrong username or password", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn issue_token(
+    data: web::Json<LoginRequest>,
+    config: web::Data<AuthConfig>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let invalid = || AppError::Unauthorized("Invalid username or password".to_string());
+    let (password_hash, role) = sqlx::query_as::<_, (String, String)>(
+        "SELECT password_hash, role FROM users WHERE username = ?",
+    )
+    .bind(&data.username)
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(invalid)?;
+
+    let parsed = PasswordHash::new(&password_hash)
+        .map_err(|e| AppError::Internal(format!("Stored password hash is invalid: {}", e)))?;
+    Argon2::default()
+        .verify_password(data.password.as_bytes(), &parsed)
+        .map_err(|_| invalid())?;
+
+    let claims = Claims {
+        sub: data.username.clone(),
+        exp: (Utc::now().timestamp() + TOKEN_TTL_SECS) as usize,
+        role: role.parse().map_err(AppError::Internal)?,
+    };
+    let access_token = jsonwebtoken::encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
+    )
+    .map_err(|e| AppError::Internal(format!("Token signing failed: {}", e)))?;
+
+    Ok(HttpResponse::Ok().json(TokenResponse {
+        access_token,
+        token_type: "Bearer".to_string(),
+        expires_in: TOKEN_TTL_SECS,
+    }))
 }
 
-// Root handler: provides basic API usage info.
-async fn index() -> impl Responder {
-    HttpResponse::Ok()
-        .body("YouthSync API: Use /attendance (POST), /report (GET), or /export (GET)")
+// POST /admin/api-keys
+// Generates a random API key for a server-to-server client. Admins only, and only with a JWT, so
+// one API key cannot be used to mint another. The plain key is returned once; only its hash is stored.
+#[utoipa::path(
+    post,
+    path = "/v1/admin/api-keys",
+    tag = "admin",
+    summary = "New API key (admin JWT only)",
+    responses(
+        (status = 201, description = "New key; the secret is only shown once", body = CreatedApiKey),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 422, description = "Validation failed", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Caller lacks the required role", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn create_api_key(
+    auth: AuthGuard,
+    data: web::Json<NewApiKey>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    if auth.method != AuthMethod::Jwt || auth.role != Role::Admin {
+        return Err(AppError::Forbidden(format!(
+            "'{}' may not create API keys; sign in as an admin",
+            auth.subject
+        )));
+    }
+    let label = data.label.trim();
+    if label.is_empty() {
+        return Err(AppError::ValidationError(
+            "label must not be empty".to_string(),
+        ));
+    }
+
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let key: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+    let mut tx = pool.begin().await?;
+    let (id, created_at) = sqlx::query_as::<_, (i64, String)>(
+        "INSERT INTO api_keys (key_hash, label) VALUES (?, ?) RETURNING id, created_at",
+    )
+    .bind(hash_api_key(&key))
+    .bind(label)
+    .fetch_one(&mut *tx)
+    .await?;
+    // The key and its hash stay out of the audit trail.
+    record_audit(
+        &mut *tx,
+        "api_keys",
+        id,
+        "INSERT",
+        None,
+        Some(serde_json::json!({ "id": id, "label": label, "created_at": created_at }).to_string()),
+        Some(&auth.subject),
+    )
+    .await?;
+    tx.commit().await?;
+
+    tracing::info!(api_key_id = id, label, created_by = %auth.subject, "API key created");
+    Ok(HttpResponse::Created().json(CreatedApiKey {
+        id,
+        label: label.to_string(),
+        key,
+        created_at,
+    }))
 }
 
-// POST /attendance
-// Accepts JSON payload to insert a new attendance record into the database.
-async fn add_attendance(
-    data: web::Json<Attendance>,
+// POST /admin/users
+// Creates a user account that can sign in through POST /auth/token. Admins only.
+#[utoipa::path(
+    post,
+    path = "/v1/admin/users",
+    tag = "admin",
+    summary = "New user account (admin only)",
+    responses(
+        (status = 201, description = "Account created", body = CreatedUser),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 422, description = "Validation failed", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Caller lacks the required role", body = ApiError),
+        (status = 409, description = "Username already taken", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn create_user(
+    RequireRole(auth, _): RequireRole<AdminOnly>,
+    data: web::Json<NewUser>,
     pool: web::Data<SqlitePool>,
-) -> impl Responder {
-    // Execute INSERT query with bound parameters from JSON request.
-    let result = sqlx::query("INSERT INTO attendance (student_id, date, status) VALUES (?, ?, ?)")
-        .bind(data.student_id)
-        .bind(&data.date)
-        .bind(&data.status)
-        .execute(pool.get_ref())
-        .await;
+) -> Result<HttpResponse, AppError> {
+    let username = data.username.trim();
+    if username.is_empty() {
+        return Err(AppError::ValidationError(
+            "username must not be empty".to_string(),
+        ));
+    }
+    if data.password.chars().count() < MIN_PASSWORD_CHARS {
+        return Err(AppError::ValidationError(format!(
+            "password must be at least {} characters",
+            MIN_PASSWORD_CHARS
+        )));
+    }
+    let role = match data.role.as_deref() {
+        Some(role) => role.parse().map_err(AppError::ValidationError)?,
+        None => Role::Staff,
+    }
+    .as_str();
 
-    // Return OK on success or InternalServerError with error message on failure.
-    match result {
-        Ok(_) => HttpResponse::Ok().body("Attendance recorded"),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Error: {}", e)),
+    let password_hash = hash_password(&data.password)?;
+    let mut tx = pool.begin().await?;
+    let user = sqlx::query_as::<_, CreatedUser>(
+        "INSERT INTO users (username, password_hash, role) VALUES (?, ?, ?) \
+         RETURNING id, username, role, created_at",
+    )
+    .bind(username)
+    .bind(password_hash)
+    .bind(role)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| {
+        if is_unique_violation(&e) {
+            AppError::Conflict(format!("User '{}' already exists", username))
+        } else {
+            AppError::DatabaseError(e)
+        }
+    })?;
+    // CreatedUser carries no password hash, so it is safe to log as the new value.
+    record_audit(
+        &mut *tx,
+        "users",
+        user.id,
+        "INSERT",
+        None,
+        serde_json::to_string(&user).ok(),
+        Some(&auth.subject),
+    )
+    .await?;
+    tx.commit().await?;
+
+    tracing::info!(user_id = user.id, username, role, created_by = %auth.subject, "user created");
+    Ok(HttpResponse::Created().json(user))
+}
+
+// POST /admin/webhooks
+// Registers a webhook. For "attendance.low", `url` receives a signed LowAttendanceEvent whenever
+// a POST /attendance drops that day's present rate below `threshold`. Admins only.
+#[utoipa::path(
+    post,
+    path = "/v1/admin/webhooks",
+    tag = "admin",
+    summary = "New webhook (admin only)",
+    request_body = NewWebhook,
+    responses(
+        (status = 201, description = "Webhook registered; the secret is not echoed", body = Webhook),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 422, description = "Validation failed", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Caller lacks the required role", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn create_webhook(
+    RequireRole(auth, _): RequireRole<AdminOnly>,
+    data: web::Json<NewWebhook>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let url = data.url.trim();
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+        _ => {
+            return Err(AppError::ValidationError(format!(
+                "url '{}' must be an absolute http or https URL",
+                url
+            )));
+        }
+    }
+    if data.secret.is_empty() {
+        return Err(AppError::ValidationError(
+            "secret must not be empty".to_string(),
+        ));
     }
+    if data.event != WEBHOOK_EVENT_LOW_ATTENDANCE {
+        return Err(AppError::ValidationError(format!(
+            "Unknown event '{}', expected '{}'",
+            data.event, WEBHOOK_EVENT_LOW_ATTENDANCE
+        )));
+    }
+    if !(0.0..=1.0).contains(&data.threshold) {
+        return Err(AppError::ValidationError(
+            "threshold must be between 0.0 and 1.0".to_string(),
+        ));
+    }
+
+    let mut tx = pool.begin().await?;
+    let webhook = sqlx::query_as::<_, Webhook>(
+        "INSERT INTO webhooks (url, secret, event, threshold) VALUES (?, ?, ?, ?) \
+         RETURNING id, url, event, threshold",
+    )
+    .bind(url)
+    .bind(&data.secret)
+    .bind(&data.event)
+    .bind(data.threshold)
+    .fetch_one(&mut *tx)
+    .await?;
+    // Webhook carries no secret, so it is safe to log as the new value.
+    record_audit(
+        &mut *tx,
+        "webhooks",
+        webhook.id,
+        "INSERT",
+        None,
+        serde_json::to_string(&webhook).ok(),
+        Some(&auth.subject),
+    )
+    .await?;
+    tx.commit().await?;
+
+    tracing::info!(webhook_id = webhook.id, event = %webhook.event, created_by = %auth.subject, "webhook created");
+    Ok(HttpResponse::Created().json(webhook))
 }
 
-// GET /report
-// Retrieves all attendance records, aggregates by day, and returns JSON array of DailyReport.
-async fn get_report(pool: web::Data<SqlitePool>) -> impl Responder {
-    // Fetch all rows from 'attendance' table into Attendance structs.
-    let records = sqlx::query_as::<_, Attendance>("SELECT * FROM attendance")
-        .fetch_all(pool.get_ref())
-        .await;
+// DELETE /admin/webhooks/{id}
+// Removes a webhook; it receives no further deliveries. Admins only.
+#[utoipa::path(
+    delete,
+    path = "/v1/admin/webhooks/{id}",
+    tag = "admin",
+    summary = "Remove a webhook (admin only)",
+    responses(
+        (status = 204, description = "Webhook removed"),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Caller lacks the required role", body = ApiError),
+        (status = 404, description = "Webhook not found", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn delete_webhook(
+    RequireRole(auth, _): RequireRole<AdminOnly>,
+    path: web::Path<i64>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
 
-    match records {
-        Ok(records) => {
-            let mut daily_counts: Vec<DailyReport> = Vec::new();
+    let mut tx = pool.begin().await?;
+    let deleted = sqlx::query_as::<_, Webhook>(
+        "DELETE FROM webhooks WHERE id = ? RETURNING id, url, event, threshold",
+    )
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Webhook {} not found", id)))?;
+    record_audit(
+        &mut *tx,
+        "webhooks",
+        id,
+        "DELETE",
+        serde_json::to_string(&deleted).ok(),
+        None,
+        Some(&auth.subject),
+    )
+    .await?;
+    tx.commit().await?;
+    tracing::info!(webhook_id = id, "webhook deleted");
+    Ok(HttpResponse::NoContent().finish())
+}
 
-            for record in records {
-                // Parse the stored date string into NaiveDate for formatting.
-                let date = match NaiveDate::parse_from_str(&record.date, "%Y-%m-%d") {
-                    Ok(date) => date,
-                    Err(e) => {
-                        return HttpResponse::InternalServerError()
-                            .body(format!("Date parse error: {}", e));
-                    }
-                };
-                // Format date as "MM-DD-YYYY" for response.
-                let formatted_date = format!("{:02}-{:02}-{}", date.month(), date.day(), date.year());
-
-                // Look for an existing entry for this date.
-                if let Some(report) = daily_counts.iter_mut().find(|r| r.date == formatted_date) {
-                    // Increment appropriate counter based on status.
-                    match record.status.as_str() {
-                        "Present" => report.present_count += 1,
-                        "Absent" => report.absent_count += 1,
-                        _ => (), // Skip invalid status values
-                    }
-                } else {
-                    // Create a new report entry if none exists for this date.
-                    daily_counts.push(DailyReport {
-                        date: formatted_date.clone(),
-                        present_count: if record.status == "Present" { 1 } else { 0 },
-                        absent_count: if record.status == "Absent" { 1 } else { 0 },
-                    });
+// GET /admin/audit-log
+// Returns one page of the audit trail, newest change first, optionally filtered by ?table_name=,
+// ?action= and ?start=/?end=. Admins only.
+#[utoipa::path(
+    get,
+    path = "/v1/admin/audit-log",
+    tag = "admin",
+    summary = "Audit trail of every change (admin only)",
+    params(AuditLogQuery, DateRangeQuery, PaginationQuery),
+    responses(
+        (status = 200, description = "One page of audit entries", body = PaginatedResponse<AuditLogEntry>),
+        (status = 400, description = "Invalid parameters", body = ApiError),
+        (status = 401, description = "Missing or invalid credentials", body = ApiError),
+        (status = 403, description = "Caller lacks the required role", body = ApiError),
+    )
+)]
+#[tracing::instrument(skip_all)]
+async fn get_audit_log(
+    _admin: RequireRole<AdminOnly>,
+    query: web::Query<AuditLogQuery>,
+    pagination: web::Query<PaginationQuery>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, AppError> {
+    let (page, per_page, offset) = pagination.resolve().map_err(AppError::BadRequest)?;
+    let (start, end) = query.range.bounds().map_err(AppError::BadRequest)?;
+    let action = query.action.as_deref().map(str::to_ascii_uppercase);
+    if let Some(action) = &action
+        && !AUDIT_ACTIONS.contains(&action.as_str())
+    {
+        return Err(AppError::BadRequest(format!(
+            "Invalid action '{}', expected INSERT, UPDATE or DELETE",
+            action
+        )));
+    }
+
+    let filter = "WHERE (?1 IS NULL OR table_name = ?1) AND (?2 IS NULL OR action = ?2) \
+                  AND date(performed_at) BETWEEN ?3 AND ?4";
+    let total = sqlx::query_scalar::<_, i64>(&format!("SELECT COUNT(*) FROM audit_log {}", filter))
+        .bind(&query.table_name)
+        .bind(&action)
+        .bind(&start)
+        .bind(&end)
+        .fetch_one(pool.get_ref())
+        .await?;
+    let rows = sqlx::query_as::<
+        _,
+        (
+            i64,
+            String,
+            i64,
+            String,
+            Option<String>,
+            Option<String>,
+            Option<String>,
+            String,
+        ),
+    >(&format!(
+        "SELECT id, table_name, record_id, action, old_value, new_value, performed_by, \
+                performed_at \
+         FROM audit_log {} ORDER BY performed_at DESC, id DESC LIMIT ?5 OFFSET ?6",
+        filter
+    ))
+    .bind(&query.table_name)
+    .bind(&action)
+    .bind(&start)
+    .bind(&end)
+    .bind(per_page)
+    .bind(offset)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    // Snapshots are stored as JSON text; embed them as JSON rather than as strings.
+    let parse = |value: Option<String>| value.and_then(|v| serde_json::from_str(&v).ok());
+    let data: Vec<AuditLogEntry> = rows
+        .into_iter()
+        .map(
+            |(id, table_name, record_id, action, old, new, performed_by, performed_at)| {
+                AuditLogEntry {
+                    id,
+                    table_name,
+                    record_id,
+                    action,
+                    old_value: parse(old),
+                    new_value: parse(new),
+                    performed_by,
+                    performed_at,
                 }
-            }
-            // Return aggregated report as JSON.
-            HttpResponse::Ok().json(daily_counts)
-        }
-        Err(e) => HttpResponse::InternalServerError().body(format!("Error: {}", e)),
+            },
+        )
+        .collect();
+    Ok(HttpResponse::Ok().json(PaginatedResponse {
+        data,
+        page,
+        per_page,
+        total,
+    }))
+}
+
+// ApiDoc is the OpenAPI description of every endpoint, built from the #[utoipa::path] attribute on
+// each handler and the ToSchema/IntoParams derives on the types they take and return. main serves
+// it at GET /openapi.json, with a Swagger UI at GET /docs.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "YouthSync",
+        description = "Attendance tracking API: record attendance, run reports and export data."
+    ),
+    paths(
+        index, health_check, readiness_check, get_metrics, issue_token, create_api_key,
+        create_user, create_webhook, delete_webhook, get_audit_log, add_attendance, add_attendance_bulk, import_csv,
+        upsert_attendance, list_attendance, get_student_attendance, list_recorded_dates,
+        get_attendance_gaps, list_deleted_attendance, restore_attendance_record, get_attendance_record,
+        delete_attendance_record, update_attendance_status, delete_attendance, get_report,
+        get_weekly_report, get_monthly_report, get_quarterly_report, get_year_summary,
+        get_heatmap, get_period_comparison, get_daily_report, get_absent_students,
+        get_present_students, get_summary_card, get_staff_recording_patterns, get_perfect_days,
+        get_today_summary, get_streak_distribution, get_attendance_by_zip,
+        get_daily_notifications, get_entry_timeliness, get_survival_curve,
+        get_attendance_by_referral, get_dashboard_metrics, get_record_audit_trail,
+        get_recovery_stories, get_group_comparison_time_series, schedule_export,
+        get_improvement_trajectories, get_data_entry_streaks, get_attendance_parity,
+        get_staff_day_patterns, get_recidivism_report, get_peer_influence,
+        get_optimal_session_day, get_monthly_grid, get_trend_by_grade, get_benefits_at_risk,
+        get_two_week_alerts, export_csv, export_json, export_report_csv, export_student_csv,
+        get_stats, bulk_update_students, get_at_risk_students, get_weekly_birthdays,
+        export_all_summaries_csv, export_student_ics, get_contact_info, put_contact_info,
+        add_intervention, get_interventions, get_incentive_eligibility, get_student_stats,
+        get_student_streak, get_siblings, create_student, list_students, get_student,
+        update_student, delete_student, list_groups, create_group, get_group_students,
+        get_group_report, list_facilitators, get_facilitator_records
+    ),
+    modifiers(&SecurityAddon),
+    security((), ("bearer" = []), ("api_key" = [])),
+    tags(
+        (name = "attendance", description = "Recording and correcting attendance records"),
+        (name = "reports", description = "Aggregated attendance reports"),
+        (name = "exports", description = "CSV, JSON and calendar exports"),
+        (name = "students", description = "Student records and per-student reports"),
+        (name = "groups", description = "Student groups"),
+        (name = "facilitators", description = "Staff credited with attendance records"),
+        (name = "auth", description = "Obtaining bearer tokens"),
+        (name = "admin", description = "Accounts, API keys and the audit log"),
+        (name = "system", description = "Health, readiness and metrics"),
+    )
+)]
+struct ApiDoc;
+
+// Declares the two credentials require_auth accepts: a bearer JWT from POST /auth/token and an
+// API key in the X-Api-Key header. Reads are open unless AUTH_PROTECT_READS is set, so
+// credentials are optional at the document level.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))),
+        );
     }
 }
 
-// GET /export
-// Exports all attendance records as a CSV file download.
-async fn export_csv(pool: web::Data<SqlitePool>) -> impl Responder {
-    // Query all attendance records.
-    let records = sqlx::query_as::<_, Attendance>("SELECT * FROM attendance")
-        .fetch_all(pool.get_ref())
-        .await;
+// Registers every versioned endpoint. main mounts these under /v1 and, deprecated, at their
+// original unversioned paths; see API_VERSIONS.
+fn api_routes(cfg: &mut web::ServiceConfig) {
+    cfg
+        .route("/auth/token", web::post().to(issue_token)) // POST credentials for a JWT.
+        .route("/admin/api-keys", web::post().to(create_api_key)) // POST new API key (admin JWT only).
+        .route("/admin/users", web::post().to(create_user)) // POST new user account (admin only).
+        .route("/admin/webhooks", web::post().to(create_webhook)) // POST new low-attendance webhook (admin only).
+        .route("/admin/webhooks/{id}", web::delete().to(delete_webhook)) // DELETE a webhook (admin only).
+        .route("/admin/audit-log", web::get().to(get_audit_log)) // GET audit trail of every change (admin only).
+        .route("/attendance", web::post().to(add_attendance)) // POST new attendance.
+        .route("/attendance/bulk", web::post().to(add_attendance_bulk)) // POST many records in one transaction.
+        .route("/import/csv", web::post().to(import_csv)) // POST multipart CSV upload.
+        .route("/attendance/upsert", web::put().to(upsert_attendance)) // PUT insert-or-update one record.
+        .route("/attendance", web::get().to(list_attendance)) // GET paginated attendance records.
+        .route("/attendance/student/{id}", web::get().to(get_student_attendance)) // GET one student's history.
+        .route("/attendance/dates", web::get().to(list_recorded_dates)) // GET distinct recorded dates.
+        .route("/attendance/gaps/{date}", web::get().to(get_attendance_gaps)) // GET students with no record on a date.
+        .route("/attendance/deleted", web::get().to(list_deleted_attendance)) // GET soft-deleted records (admin).
+        .route("/attendance/{id}/restore", web::post().to(restore_attendance_record)) // POST undo a soft delete (admin).
+        .route("/attendance/{id}", web::get().to(get_attendance_record)) // GET one record by id.
+        .route("/attendance/{id}", web::delete().to(delete_attendance_record)) // DELETE one record by id.
+        .route("/attendance/{student_id}/{date}", web::put().to(update_attendance_status)) // PUT corrected status.
+        .route("/attendance/{student_id}/{date}", web::delete().to(delete_attendance)) // DELETE one record.
+        .route("/report", web::get().to(get_report))         // GET aggregated report.
+        .route("/report/weekly", web::get().to(get_weekly_report)) // GET ISO-week report.
+        .route("/report/monthly", web::get().to(get_monthly_report)) // GET month-over-month report.
+        .route("/report/quarterly", web::get().to(get_quarterly_report)) // GET quarter-over-quarter report.
+        .route("/report/year/{year}", web::get().to(get_year_summary)) // GET one year's summary.
+        .route("/report/heatmap", web::get().to(get_heatmap)) // GET daily present rates for a year.
+        .route("/report/compare", web::get().to(get_period_comparison)) // GET two date ranges side by side.
+        .route("/report/daily/{date}", web::get().to(get_daily_report)) // GET one day's report.
+        .route("/report/absent/{date}", web::get().to(get_absent_students)) // GET students absent on a date.
+        .route("/report/present/{date}", web::get().to(get_present_students)) // GET students present on a date.
+        .route("/report/attendance-summary-card", web::get().to(get_summary_card)) // GET mobile summary card.
+        .route("/report/attendance-by-staff-member", web::get().to(get_staff_recording_patterns)) // GET staff recording patterns.
+        .route("/report/attendance-goal-met-days", web::get().to(get_perfect_days)) // GET perfect-attendance days.
+        .route("/report/today", web::get().to(get_today_summary)) // GET today's summary.
+        .route("/report/daily-attendance-summary", web::get().to(get_today_summary)) // Alias of /report/today.
+        .route("/report/attendance-streak-distribution", web::get().to(get_streak_distribution)) // GET streak distribution.
+        .route("/report/attendance-by-zip-code", web::get().to(get_attendance_by_zip)) // GET rates by ZIP code.
+        .route("/report/daily-notifications", web::get().to(get_daily_notifications)) // GET today's alerts.
+        .route("/report/data-entry-timeliness", web::get().to(get_entry_timeliness)) // GET entry delays.
+        .route("/report/cohort-survival-curve", web::get().to(get_survival_curve)) // GET cohort retention.
+        .route("/report/attendance-by-referral-source", web::get().to(get_attendance_by_referral)) // GET rates by referral source.
+        .route("/report/program-metrics-dashboard", web::get().to(get_dashboard_metrics)) // GET executive KPIs.
+        .route("/report/attendance-audit-trail", web::get().to(get_record_audit_trail)) // GET record history.
+        .route("/report/attendance-recovery", web::get().to(get_recovery_stories)) // GET improving students.
+        .route("/report/group-comparison-over-time", web::get().to(get_group_comparison_time_series)) // GET weekly rates per group.
+        .route("/report/export-scheduled", web::post().to(schedule_export)) // POST recurring export.
+        .route("/report/attendance-improvement-over-enrollment", web::get().to(get_improvement_trajectories)) // GET student trajectories.
+        .route("/report/consecutive-days-data-entry", web::get().to(get_data_entry_streaks)) // GET data-entry streak.
+        .route("/report/attendance-parity", web::get().to(get_attendance_parity)) // GET subgroup parity.
+        .route("/report/attendance-by-staff-day-of-week", web::get().to(get_staff_day_patterns)) // GET staff coverage by weekday.
+        .route("/report/attendance-recidivism", web::get().to(get_recidivism_report)) // GET re-enrollment events.
+        .route("/report/peer-influence", web::get().to(get_peer_influence)) // GET co-absent student pairs.
+        .route("/report/optimal-session-day", web::get().to(get_optimal_session_day)) // GET best weekday.
+        .route("/report/all-students-monthly-grid", web::get().to(get_monthly_grid)) // GET students x months grid.
+        .route("/report/attendance-trend-by-grade", web::get().to(get_trend_by_grade)) // GET weekly rate trend per grade.
+        .route("/report/students-at-risk-of-losing-benefits", web::get().to(get_benefits_at_risk)) // GET students below benefits threshold.
+        .route("/report/two-week-absence-alert", web::get().to(get_two_week_alerts)) // GET students unseen for two weeks.
+        .route("/export", web::get().to(export_csv))         // GET CSV export.
+        .route("/export/json", web::get().to(export_json)) // GET JSON export.
+        .route("/export/report", web::get().to(export_report_csv)) // GET daily report as CSV.
+        .route("/export/student/{id}", web::get().to(export_student_csv)) // GET one student's CSV.
+        .route("/stats", web::get().to(get_stats))           // GET overall data statistics.
+        .route("/students/bulk", web::patch().to(bulk_update_students)) // PATCH several students.
+        .route("/students/at-risk", web::get().to(get_at_risk_students)) // GET students over an absence threshold.
+        .route("/students/birthdays-this-week", web::get().to(get_weekly_birthdays)) // GET this week's birthdays.
+        .route("/students/export-all-summaries", web::get().to(export_all_summaries_csv)) // GET per-student CSV.
+        .route("/students/{id}/attendance-export-ics", web::get().to(export_student_ics)) // GET student calendar.
+        .route("/students/{id}/contact-info", web::get().to(get_contact_info)) // GET contact details (admin only).
+        .route("/students/{id}/contact-info", web::put().to(put_contact_info)) // PUT contact details (admin only).
+        .route("/students/{id}/interventions", web::post().to(add_intervention)) // POST new intervention.
+        .route("/students/{id}/interventions", web::get().to(get_interventions)) // GET interventions.
+        .route("/students/{id}/intervention-history", web::get().to(get_interventions)) // Alias of the above.
+        .route("/students/{id}/incentive-eligibility", web::get().to(get_incentive_eligibility)) // GET incentive tier.
+        .route("/students/{id}/stats", web::get().to(get_student_stats)) // GET attendance counts and rate.
+        .route("/students/{id}/streak", web::get().to(get_student_streak)) // GET current and longest streaks.
+        .route("/students/{id}/siblings", web::get().to(get_siblings)) // GET students in the same family.
+        .route("/students", web::post().to(create_student)) // POST new student.
+        .route("/students", web::get().to(list_students)) // GET all students.
+        .route("/students/{id}", web::get().to(get_student)) // GET one student.
+        .route("/students/{id}", web::put().to(update_student)) // PUT student fields.
+        .route("/students/{id}", web::delete().to(delete_student)) // DELETE student without attendance.
+        .route("/groups", web::get().to(list_groups)) // GET all groups.
+        .route("/groups", web::post().to(create_group)) // POST new group.
+        .route("/groups/{id}/students", web::get().to(get_group_students)) // GET students in a group.
+        .route("/groups/{id}/report", web::get().to(get_group_report)) // GET a group's attendance summary.
+        .route("/facilitators", web::get().to(list_facilitators)) // GET all facilitators.
+        .route("/facilitators/{id}/records", web::get().to(get_facilitator_records)); // GET records credited to a facilitator.
+}
 
-    match records {
-        Ok(records) => {
-            // Initialize CSV writer over an in-memory buffer.
-            let mut wtr = Writer::from_writer(vec![]);
-            // Write CSV header row.
-            wtr.write_record(["Student ID", "Date", "Status"]).unwrap();
+// Seconds the server waits for in-flight requests to finish after a shutdown signal.
+const SHUTDOWN_TIMEOUT_SECS: u64 = 30;
 
-            // Write each record as a new CSV row.
-            for record in records {
-                wtr.write_record(&[record.student_id.to_string(), record.date, record.status])
-                    .unwrap();
+// Waits for Ctrl-C (SIGINT) or SIGTERM, then stops the server gracefully: it stops accepting
+// connections and lets in-flight requests finish for up to SHUTDOWN_TIMEOUT_SECS.
+async fn stop_on_signal(handle: actix_web::dev::ServerHandle) {
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "cannot listen for SIGTERM");
+                std::future::pending::<()>().await;
             }
-
-            // Return response with CSV content and proper content type.
-            HttpResponse::Ok()
-                .content_type("text/csv")
-                .body(wtr.into_inner().unwrap())
         }
-        Err(e) => HttpResponse::InternalServerError().body(format!("Error: {}", e)),
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => tracing::info!(signal = "SIGINT", "shutdown requested"),
+        _ = terminate => tracing::info!(signal = "SIGTERM", "shutdown requested"),
     }
+    tracing::info!(
+        timeout_secs = SHUTDOWN_TIMEOUT_SECS,
+        "no longer accepting connections, draining in-flight requests"
+    );
+    handle.stop(true).await;
 }
 
 // Main entry point: sets up database connection, runs migrations, and starts the HTTP server.
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    // Print current working directory for debugging purposes.
-    println!("Current directory: {:?}", std::env::current_dir());
+    // Read every setting once; any invalid variable aborts startup.
+    let config =
+        Config::from_env().map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    init_tracing(&config.log_level, config.log_json);
+    // Log the current working directory and effective settings for debugging purposes.
+    tracing::info!(
+        cwd = ?std::env::current_dir(),
+        database_url = %config.database_url,
+        pool_min_connections = config.pool_min_connections,
+        pool_max_connections = config.pool_max_connections,
+        cors_allowed_origins = ?config.cors_allowed_origins,
+        "starting YouthSync"
+    );
+    if std::env::var_os("JWT_SECRET").is_none() {
+        tracing::warn!("JWT_SECRET is not set; using a random secret for this run");
+    }
 
     // Initialize SQLite connection pool, creating the DB file if missing.
+    let options = config
+        .connect_options()
+        .map_err(|e| std::io::Error::other(format!("Invalid DATABASE_URL: {}", e)))?;
+    let (wal_mode, busy_timeout_ms) = (config.wal_mode, config.busy_timeout_ms);
     let pool = match sqlx::sqlite::SqlitePoolOptions::new()
-        .connect_with(
-            sqlx::sqlite::SqliteConnectOptions::new()
-                .filename("./youthsync.db")
-                .create_if_missing(true),
-        )
+        .min_connections(config.pool_min_connections)
+        .max_connections(config.pool_max_connections)
+        .acquire_timeout(POOL_ACQUIRE_TIMEOUT)
+        .after_connect(move |conn, _| {
+            Box::pin(apply_sqlite_pragmas(conn, wal_mode, busy_timeout_ms))
+        })
+        .connect_with(options)
         .await
     {
         Ok(pool) => pool,
         Err(e) => {
-            eprintln!("Failed to connect to database: {}", e);
+            tracing::error!(error = %e, "failed to connect to database");
             // Return an error to abort startup.
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Database connection failed",
-            ));
+            return Err(std::io::Error::other("Database connection failed"));
         }
     };
 
+    // Confirm the PRAGMAs took effect.
+    if let Err(e) = log_sqlite_pragmas(&pool, config.wal_mode).await {
+        tracing::warn!(error = %e, "could not read SQLite settings");
+    }
+
     // Execute SQL migrations located in the ./migrations directory.
     if let Err(e) = sqlx::migrate!("./migrations").run(&pool).await {
-        eprintln!("Failed to run migrations: {}", e);
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Migration failed",
-        ));
+        tracing::error!(error = %e, "failed to run migrations");
+        return Err(std::io::Error::other("Migration failed"));
     }
 
+    // Create the bootstrap admin account if one is configured.
+    if let Some((username, password)) = &config.admin_credentials
+        && let Err(e) = seed_admin_user(&pool, username, password).await
+    {
+        tracing::error!(error = %e, "failed to create admin user");
+        return Err(std::io::Error::other("Admin user setup failed"));
+    }
+
+    let repository: Arc<dyn AttendanceRepository + Send + Sync> =
+        Arc::new(SqliteRepository { pool: pool.clone() });
+
+    let auth_config = web::Data::new(config.auth.clone());
+    let rate_limiter = web::Data::new(RateLimiter::new(
+        config.rate_limit_requests,
+        config.rate_limit_window_secs,
+    ));
+    let report_cache = web::Data::new(ReportCache::new(REPORT_CACHE_CAPACITY, REPORT_CACHE_TTL));
+    let openapi = ApiDoc::openapi();
+    let webhook_client = match reqwest::Client::builder().timeout(WEBHOOK_TIMEOUT).build() {
+        Ok(client) => web::Data::new(client),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to build webhook client");
+            return Err(std::io::Error::other("Webhook client setup failed"));
+        }
+    };
+
+    let metrics = match Metrics::new() {
+        Ok(metrics) => web::Data::new(metrics),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to register metrics");
+            return Err(std::io::Error::other("Metrics setup failed"));
+        }
+    };
+
+    // Start the scheduler that runs recurring exports in the background.
+    let mailer = match &config.mail {
+        Some(mail) => match mail.transport() {
+            Ok(transport) => Some(Mailer {
+                transport,
+                from: mail.from.clone(),
+            }),
+            Err(e) => {
+                tracing::error!(error = %e, "failed to set up SMTP");
+                return Err(std::io::Error::other("SMTP setup failed"));
+            }
+        },
+        None => {
+            tracing::warn!("SMTP_HOST is not set; scheduled exports will not be emailed");
+            None
+        }
+    };
+    tokio::spawn(run_scheduled_exports(
+        pool.clone(),
+        config.export_dir.clone(),
+        mailer,
+    ));
+    // Expire stored idempotency keys in the background.
+    tokio::spawn(run_idempotency_key_cleanup(repository.clone()));
+
+    tracing::info!(host = %config.host, port = config.port, "listening");
+    let bind_addr = (config.host.clone(), config.port);
+    let config = web::Data::new(config);
+    let shutdown_pool = pool.clone();
+
     // Build and run the Actix HTTP server.
-    HttpServer::new(move || {
+    let server = HttpServer::new(move || {
         App::new()
-            .wrap(Cors::permissive())           // Allow all CORS requests for simplicity.
+            .wrap(middleware::from_fn(invalidate_report_cache)) // Drop cached GET /report bodies after attendance changes.
+            .wrap(middleware::from_fn(report_etag)) // ETag and Cache-Control on GET /report responses.
+            .wrap(middleware::from_fn(require_auth)) // Require a bearer token or API key for writes (and reads if configured).
+            .wrap(middleware::from_fn(rate_limit)) // Throttle writes per client IP before authenticating.
+            .wrap(middleware::Condition::new(config.compression_enabled, middleware::Compress::default())) // Compress responses per Accept-Encoding unless COMPRESSION_ENABLED=false.
+            .wrap(config.cors())                // CORS policy from CORS_ALLOWED_* (permissive by default).
+            .wrap(middleware::from_fn(log_request_timing)) // Log request durations; warn above SLOW_REQUEST_THRESHOLD_MS.
+            .wrap(middleware::from_fn(track_metrics)) // Count requests and their latency for GET /metrics.
+            .wrap(middleware::from_fn(request_id)) // Tag every request and its log lines with a UUID.
             .app_data(web::Data::new(pool.clone())) // Share DB pool with handlers.
-            .route("/", web::get().to(index))       // Root health-check / info endpoint.
-            .route("/attendance", web::post().to(add_attendance)) // POST new attendance.
-            .route("/report", web::get().to(get_report))         // GET aggregated report.
-            .route("/export", web::get().to(export_csv))         // GET CSV export.
+            .app_data(web::Data::new(repository.clone())) // Share the attendance store.
+            .app_data(metrics.clone())          // Share Prometheus metric handles.
+            .app_data(config.clone())           // Share startup settings.
+            .app_data(auth_config.clone())      // Share JWT secret and access policy.
+            .app_data(rate_limiter.clone())     // Share per-IP write counters across workers.
+            .app_data(report_cache.clone())     // Share cached GET /report bodies across workers.
+            .app_data(webhook_client.clone())   // Share the HTTP client that delivers webhooks.
+            .app_data(web::JsonConfig::default().limit(MAX_BODY_BYTES).error_handler(json_error)) // JSON errors for bad or oversized bodies.
+            .app_data(web::PayloadConfig::new(MAX_BODY_BYTES)) // Cap raw request bodies too.
+            .app_data(web::QueryConfig::default().error_handler(|e, _| AppError::BadRequest(e.to_string()).into())) // JSON errors for bad query strings.
+            .app_data(web::PathConfig::default().error_handler(|e, _| AppError::BadRequest(e.to_string()).into())) // JSON errors for bad path segments.
+            .route("/", web::get().to(index))       // GET available API versions.
+            .route("/health", web::get().to(health_check)) // GET liveness probe (database ping).
+            .route("/ready", web::get().to(readiness_check)) // GET readiness probe (ping plus migrations).
+            .route("/metrics", web::get().to(get_metrics)) // GET Prometheus metrics.
+            .service(web::redirect("/docs", "/docs/")) // GET /docs without the trailing slash.
+            .service(SwaggerUi::new("/docs/{_:.*}").url("/openapi.json", openapi.clone())) // GET Swagger UI at /docs/ and the spec at /openapi.json.
+            .service(web::scope("/v1").configure(api_routes)) // Current API version.
+            .service(web::scope("").wrap(middleware::DefaultHeaders::new().add((DEPRECATED_HEADER, "true"))).configure(api_routes)) // Unversioned paths, deprecated; must stay last as it matches every path.
     })
-    .bind("127.0.0.1:8080")? // Bind to localhost on port 8080.
-    .run()
-    .await
+    .bind(bind_addr)? // Bind to HOST:PORT.
+    .shutdown_timeout(SHUTDOWN_TIMEOUT_SECS) // Give in-flight requests time to finish on stop.
+    .disable_signals() // Signals are handled by stop_on_signal instead.
+    .run();
+    tokio::spawn(stop_on_signal(server.handle()));
+    server.await?;
+
+    // Every worker has finished; closing the pool checkpoints SQLite's WAL into the database file.
+    tracing::info!("server stopped, closing database pool");
+    shutdown_pool.close().await;
+    tracing::info!("shutdown complete");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+
+    // Creates a migrated in-memory database. A single connection keeps every query on the
+    // same in-memory instance.
+    async fn test_pool() -> SqlitePool {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("in-memory database");
+        sqlx::migrate!("./migrations")
+            .run(&pool)
+            .await
+            .expect("migrations");
+        pool
+    }
+
+    // Marks a test request as coming from an authenticated caller with the given role, as
+    // require_auth would.
+    fn authenticate(req: &impl HttpMessage, role: Role) {
+        req.extensions_mut().insert(AuthGuard {
+            subject: "tester".to_string(),
+            role,
+            method: AuthMethod::Jwt,
+        });
+    }
+
+    // Status of a request that middleware may reject before it reaches a handler.
+    async fn status_of<S, B>(app: &S, req: actix_http::Request) -> StatusCode
+    where
+        S: actix_web::dev::Service<
+                actix_http::Request,
+                Response = ServiceResponse<B>,
+                Error = actix_web::Error,
+            >,
+    {
+        match test::try_call_service(app, req).await {
+            Ok(resp) => resp.status(),
+            Err(e) => e.as_response_error().status_code(),
+        }
+    }
+
+    // Auth settings shared by the middleware tests; reads stay open.
+    fn test_auth_config() -> web::Data<AuthConfig> {
+        web::Data::new(AuthConfig {
+            jwt_secret: "test-secret".to_string(),
+            protect_reads: false,
+        })
+    }
+
+    #[actix_web::test]
+    async fn delete_attendance_removes_matching_record() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO students (id, name) VALUES (1, 'Ana')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO attendance (student_id, date, status) VALUES (1, '2024-01-15', 'Present')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let app = test::init_service(App::new().app_data(web::Data::new(pool.clone())).route(
+            "/attendance/{student_id}/{date}",
+            web::delete().to(delete_attendance),
+        ))
+        .await;
+
+        let req = test::TestRequest::delete()
+            .uri("/attendance/1/2024-01-15")
+            .to_request();
+        authenticate(&req, Role::Admin);
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::OK);
+
+        let remaining = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM active_attendance")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, 0);
+        let deleted = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM attendance WHERE deleted_at IS NOT NULL",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(deleted, 1);
+    }
+
+    #[actix_web::test]
+    async fn delete_attendance_returns_404_when_nothing_matches() {
+        let pool = test_pool().await;
+        let app = test::init_service(App::new().app_data(web::Data::new(pool)).route(
+            "/attendance/{student_id}/{date}",
+            web::delete().to(delete_attendance),
+        ))
+        .await;
+
+        let req = test::TestRequest::delete()
+            .uri("/attendance/1/2024-01-15")
+            .to_request();
+        authenticate(&req, Role::Admin);
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn delete_attendance_requires_admin() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO students (id, name) VALUES (1, 'Ana')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO attendance (student_id, date, status) VALUES (1, '2024-01-15', 'Present')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let app = test::init_service(App::new().app_data(web::Data::new(pool.clone())).route(
+            "/attendance/{student_id}/{date}",
+            web::delete().to(delete_attendance),
+        ))
+        .await;
+
+        let req = test::TestRequest::delete()
+            .uri("/attendance/1/2024-01-15")
+            .to_request();
+        authenticate(&req, Role::Staff);
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::FORBIDDEN);
+
+        let remaining = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM active_attendance")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, 1);
+    }
+
+    #[actix_web::test]
+    async fn list_attendance_filters_by_group_through_repository() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO groups (id, name) VALUES (1, 'Tuesday')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO students (id, name, group_id) VALUES (1, 'Ana', 1), (2, 'Ben', NULL)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO attendance (student_id, date, status) \
+             VALUES (1, '2024-01-15', 'Present'), (2, '2024-01-15', 'Absent')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let repository: Arc<dyn AttendanceRepository + Send + Sync> =
+            Arc::new(SqliteRepository { pool: pool.clone() });
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .app_data(web::Data::new(repository))
+                .route("/attendance", web::get().to(list_attendance)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/attendance?group_id=1")
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["total"], 1);
+        assert_eq!(body["data"][0]["student_id"], 1);
+    }
+
+    #[actix_web::test]
+    async fn attendance_gaps_lists_students_without_a_record() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO students (id, name) VALUES (1, 'Ana'), (2, 'Ben'), (3, 'Cy')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO attendance (student_id, date, status) \
+             VALUES (1, '2024-01-15', 'Absent'), (2, '2024-01-16', 'Present')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let app = test::init_service(App::new().app_data(web::Data::new(pool)).route(
+            "/attendance/gaps/{date}",
+            web::get().to(get_attendance_gaps),
+        ))
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/attendance/gaps/2024-01-15")
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            body,
+            serde_json::json!([
+                {"student_id": 2, "name": "Ben"},
+                {"student_id": 3, "name": "Cy"},
+            ])
+        );
+    }
+
+    // A repository whose first Idempotency-Key lookup misses, as when a concurrent request with
+    // the same key commits just after add_attendance checked for it.
+    struct RacingRepository {
+        inner: SqliteRepository,
+        hide_next_lookup: std::sync::atomic::AtomicBool,
+    }
+
+    impl AttendanceRepository for RacingRepository {
+        fn insert<'a>(
+            &'a self,
+            record: &'a Attendance,
+            performed_by: &'a str,
+            idempotency_key: Option<&'a str>,
+        ) -> BoxFuture<'a, Result<i64, sqlx::Error>> {
+            self.inner.insert(record, performed_by, idempotency_key)
+        }
+
+        fn idempotent_response<'a>(
+            &'a self,
+            key: &'a str,
+        ) -> BoxFuture<'a, Result<Option<(i64, String)>, sqlx::Error>> {
+            if self
+                .hide_next_lookup
+                .swap(false, std::sync::atomic::Ordering::SeqCst)
+            {
+                return Box::pin(async { Ok(None) });
+            }
+            self.inner.idempotent_response(key)
+        }
+
+        fn expire_idempotency_keys(&self) -> BoxFuture<'_, Result<u64, sqlx::Error>> {
+            self.inner.expire_idempotency_keys()
+        }
+
+        fn fetch_all<'a>(
+            &'a self,
+            student_ids: Option<&'a [i32]>,
+            limit: i64,
+            offset: i64,
+        ) -> BoxFuture<'a, Result<(i64, Vec<Attendance>), sqlx::Error>> {
+            self.inner.fetch_all(student_ids, limit, offset)
+        }
+
+        fn fetch_by_student<'a>(
+            &'a self,
+            student_id: i32,
+            start: &'a str,
+            end: &'a str,
+        ) -> BoxFuture<'a, Result<Option<Vec<Attendance>>, sqlx::Error>> {
+            self.inner.fetch_by_student(student_id, start, end)
+        }
+
+        fn delete<'a>(
+            &'a self,
+            id: i64,
+            performed_by: &'a str,
+        ) -> BoxFuture<'a, Result<bool, sqlx::Error>> {
+            self.inner.delete(id, performed_by)
+        }
+
+        fn aggregate_daily<'a>(
+            &'a self,
+            start: &'a str,
+            end: &'a str,
+            limit: i64,
+            offset: i64,
+        ) -> BoxFuture<'a, Result<(i64, Vec<DailyTotals>), sqlx::Error>> {
+            self.inner.aggregate_daily(start, end, limit, offset)
+        }
+    }
+
+    // Serves POST /attendance on top of `pool` and `repository`, with student 1 enrolled.
+    async fn attendance_app(
+        pool: SqlitePool,
+        repository: Arc<dyn AttendanceRepository + Send + Sync>,
+    ) -> impl actix_web::dev::Service<
+        actix_http::Request,
+        Response = ServiceResponse,
+        Error = actix_web::Error,
+    > {
+        sqlx::query("INSERT INTO students (id, name) VALUES (1, 'Ana')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .app_data(web::Data::new(repository))
+                .app_data(web::Data::new(Metrics::new().unwrap()))
+                .app_data(web::Data::new(reqwest::Client::new()))
+                .route("/attendance", web::post().to(add_attendance)),
+        )
+        .await
+    }
+
+    // POSTs one attendance record for student 1, with `key` as its Idempotency-Key if given.
+    async fn post_attendance(
+        app: &impl actix_web::dev::Service<
+            actix_http::Request,
+            Response = ServiceResponse,
+            Error = actix_web::Error,
+        >,
+        key: Option<&str>,
+    ) -> (StatusCode, String) {
+        let mut req = test::TestRequest::post()
+            .uri("/attendance")
+            .set_json(serde_json::json!({
+                "student_id": 1,
+                "date": "2024-01-15",
+                "status": "Present",
+            }));
+        if let Some(key) = key {
+            req = req.insert_header((IDEMPOTENCY_KEY_HEADER, key));
+        }
+        let req = req.to_request();
+        authenticate(&req, Role::Admin);
+        let resp = test::call_service(app, req).await;
+        let status = resp.status();
+        let body = test::read_body(resp).await;
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[actix_web::test]
+    async fn idempotency_key_replays_the_first_response() {
+        let pool = test_pool().await;
+        let repository: Arc<dyn AttendanceRepository + Send + Sync> =
+            Arc::new(SqliteRepository { pool: pool.clone() });
+        let app = attendance_app(pool.clone(), repository).await;
+        let key = Uuid::new_v4().to_string();
+
+        let first = post_attendance(&app, Some(&key)).await;
+        let retry = post_attendance(&app, Some(&key)).await;
+        assert_eq!(first, (StatusCode::OK, ATTENDANCE_RECORDED.to_string()));
+        assert_eq!(retry, first);
+        // Without the key the same record is a duplicate.
+        assert_eq!(post_attendance(&app, None).await.0, StatusCode::CONFLICT);
+
+        let records = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM active_attendance")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(records, 1);
+    }
+
+    #[actix_web::test]
+    async fn idempotency_key_replays_a_response_stored_concurrently() {
+        let pool = test_pool().await;
+        let repository = Arc::new(RacingRepository {
+            inner: SqliteRepository { pool: pool.clone() },
+            hide_next_lookup: std::sync::atomic::AtomicBool::new(false),
+        });
+        let app = attendance_app(pool.clone(), repository.clone()).await;
+        let key = Uuid::new_v4().to_string();
+        assert_eq!(post_attendance(&app, Some(&key)).await.0, StatusCode::OK);
+
+        // The retry's lookup misses, so it reaches the insert and hits the stored record.
+        repository
+            .hide_next_lookup
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(
+            post_attendance(&app, Some(&key)).await,
+            (StatusCode::OK, ATTENDANCE_RECORDED.to_string())
+        );
+    }
+
+    #[actix_web::test]
+    async fn idempotency_key_must_be_a_uuid() {
+        let pool = test_pool().await;
+        let repository: Arc<dyn AttendanceRepository + Send + Sync> =
+            Arc::new(SqliteRepository { pool: pool.clone() });
+        let app = attendance_app(pool.clone(), repository).await;
+
+        assert_eq!(
+            post_attendance(&app, Some("not-a-uuid")).await.0,
+            StatusCode::BAD_REQUEST
+        );
+        let records = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM active_attendance")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(records, 0);
+    }
+
+    #[actix_web::test]
+    async fn expired_idempotency_keys_are_deleted() {
+        let pool = test_pool().await;
+        sqlx::query(
+            "INSERT INTO idempotency_keys (key, response_status, response_body, created_at) \
+             VALUES ('old', 200, 'ok', datetime('now', '-25 hours')), \
+                    ('new', 200, 'ok', datetime('now', '-23 hours'))",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let repository = SqliteRepository { pool: pool.clone() };
+
+        assert_eq!(repository.expire_idempotency_keys().await.unwrap(), 1);
+        assert!(
+            repository
+                .idempotent_response("old")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            repository
+                .idempotent_response("new")
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[actix_web::test]
+    async fn require_auth_ignores_bad_tokens_where_credentials_are_optional() {
+        let app = test::init_service(
+            App::new()
+                .wrap(middleware::from_fn(require_auth))
+                .app_data(test_auth_config())
+                .app_data(web::Data::new(test_pool().await))
+                .route("/health", web::get().to(HttpResponse::Ok))
+                .route("/auth/token", web::post().to(HttpResponse::Ok))
+                .route("/students", web::get().to(HttpResponse::Ok))
+                .route("/students", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        for (method, path, expected) in [
+            (Method::GET, "/health", StatusCode::OK),
+            (Method::POST, "/auth/token", StatusCode::OK),
+            (Method::GET, "/students", StatusCode::OK),
+            (Method::POST, "/students", StatusCode::UNAUTHORIZED),
+        ] {
+            let req = test::TestRequest::default()
+                .method(method.clone())
+                .uri(path)
+                .insert_header((header::AUTHORIZATION, "Bearer expired-or-garbage"))
+                .to_request();
+            assert_eq!(status_of(&app, req).await, expected, "{} {}", method, path);
+        }
+    }
+
+    #[actix_web::test]
+    async fn ics_values_are_escaped_and_folded() {
+        assert_eq!(ics_text("a,b;c\\d\r\ne"), "a\\,b\\;c\\\\d\\ne");
+        assert_eq!(ics_param("Kids \"Club\"^\nInc"), "Kids ^'Club^'^^^nInc");
+
+        let mut ics = String::new();
+        push_ics_line(&mut ics, &format!("DESCRIPTION:{}", "x".repeat(100)));
+        let lines: Vec<&str> = ics.trim_end_matches("\r\n").split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= 75));
+        assert!(lines[1].starts_with(' '));
+    }
+
+    #[actix_web::test]
+    async fn api_key_authenticates_like_a_token() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO api_keys (key_hash, label) VALUES (?, 'sis-sync')")
+            .bind(hash_api_key("plain-key"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .wrap(middleware::from_fn(require_auth))
+                .app_data(test_auth_config())
+                .app_data(web::Data::new(pool))
+                .route(
+                    "/students",
+                    web::post()
+                        .to(|auth: AuthGuard| async move { HttpResponse::Ok().body(auth.subject) }),
+                ),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/students")
+            .insert_header((API_KEY_HEADER, "plain-key"))
+            .to_request();
+        assert_eq!(
+            test::call_and_read_body(&app, req).await,
+            "api-key:sis-sync"
+        );
+
+        for key in [Some("wrong-key"), None] {
+            let mut req = test::TestRequest::post().uri("/students");
+            if let Some(key) = key {
+                req = req.insert_header((API_KEY_HEADER, key));
+            }
+            assert_eq!(
+                status_of(&app, req.to_request()).await,
+                StatusCode::UNAUTHORIZED
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn roles_limit_what_a_token_may_do() {
+        let token = |role: Role| {
+            let claims = Claims {
+                sub: "tester".to_string(),
+                exp: (Utc::now().timestamp() + 60) as usize,
+                role,
+            };
+            let secret = EncodingKey::from_secret(test_auth_config().jwt_secret.as_bytes());
+            format!(
+                "Bearer {}",
+                jsonwebtoken::encode(&Header::default(), &claims, &secret).unwrap()
+            )
+        };
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO students (id, name) VALUES (1, 'Ana')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO api_keys (key_hash, label) VALUES (?, 'sis-sync')")
+            .bind(hash_api_key("plain-key"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        let repository: Arc<dyn AttendanceRepository + Send + Sync> =
+            Arc::new(SqliteRepository { pool: pool.clone() });
+        let app = test::init_service(
+            App::new()
+                .wrap(middleware::from_fn(require_auth))
+                .app_data(test_auth_config())
+                .app_data(web::Data::new(pool))
+                .app_data(web::Data::new(repository))
+                .app_data(web::Data::new(Metrics::new().unwrap()))
+                .app_data(web::Data::new(reqwest::Client::new()))
+                .route("/attendance", web::post().to(add_attendance))
+                .route("/students", web::get().to(HttpResponse::Ok))
+                .route("/students", web::post().to(HttpResponse::Ok))
+                .route(
+                    "/admin/users",
+                    web::post()
+                        .to(|_admin: RequireRole<AdminOnly>| async { HttpResponse::Ok().finish() }),
+                ),
+        )
+        .await;
+
+        for (role, method, path, expected) in [
+            (Role::Viewer, Method::GET, "/students", StatusCode::OK),
+            (
+                Role::Viewer,
+                Method::POST,
+                "/students",
+                StatusCode::FORBIDDEN,
+            ),
+            (Role::Staff, Method::POST, "/students", StatusCode::OK),
+            (
+                Role::Staff,
+                Method::POST,
+                "/admin/users",
+                StatusCode::FORBIDDEN,
+            ),
+            (Role::Admin, Method::POST, "/admin/users", StatusCode::OK),
+            (
+                Role::Viewer,
+                Method::POST,
+                "/attendance",
+                StatusCode::FORBIDDEN,
+            ),
+        ] {
+            let req = test::TestRequest::default()
+                .method(method.clone())
+                .uri(path)
+                .insert_header((header::AUTHORIZATION, token(role)))
+                .to_request();
+            assert_eq!(
+                status_of(&app, req).await,
+                expected,
+                "{:?} {} {}",
+                role,
+                method,
+                path
+            );
+        }
+
+        // API keys act as staff, who record attendance; account management stays admin-only.
+        for (path, body, expected) in [
+            (
+                "/attendance",
+                serde_json::json!({"student_id": 1, "date": "2024-01-15", "status": "Present"}),
+                StatusCode::OK,
+            ),
+            ("/admin/users", serde_json::json!({}), StatusCode::FORBIDDEN),
+        ] {
+            let req = test::TestRequest::post()
+                .uri(path)
+                .insert_header((API_KEY_HEADER, "plain-key"))
+                .set_json(body)
+                .to_request();
+            assert_eq!(
+                status_of(&app, req).await,
+                expected,
+                "API key POST {}",
+                path
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn rejected_requests_carry_a_request_id() {
+        let app = test::init_service(
+            App::new()
+                .wrap(middleware::from_fn(require_auth))
+                .wrap(middleware::from_fn(request_id))
+                .app_data(test_auth_config())
+                .app_data(web::Data::new(test_pool().await))
+                .route("/students", web::get().to(HttpResponse::Ok))
+                .route("/students", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        for (method, expected) in [
+            (Method::GET, StatusCode::OK),
+            (Method::POST, StatusCode::UNAUTHORIZED),
+        ] {
+            let req = test::TestRequest::default()
+                .method(method.clone())
+                .uri("/students")
+                .to_request();
+            // Middleware rejections leave as errors; actix renders them with error_response.
+            let resp = match test::try_call_service(&app, req).await {
+                Ok(resp) => resp.into_parts().1.map_into_boxed_body(),
+                Err(e) => e.error_response(),
+            };
+            assert_eq!(resp.status(), expected);
+            let id = resp.headers().get("x-request-id").expect("X-Request-Id");
+            assert!(Uuid::parse_str(id.to_str().unwrap()).is_ok(), "{}", method);
+        }
+    }
+
+    #[actix_web::test]
+    async fn rate_limit_rejects_writes_over_the_allowance() {
+        let app = test::init_service(
+            App::new()
+                .wrap(middleware::from_fn(rate_limit))
+                .app_data(web::Data::new(RateLimiter::new(2, 60)))
+                .route("/attendance", web::get().to(HttpResponse::Ok))
+                .route("/attendance", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+        let request = |method: Method, ip: &str| {
+            test::TestRequest::default()
+                .method(method)
+                .uri("/attendance")
+                .peer_addr(format!("{}:4000", ip).parse().unwrap())
+                .to_request()
+        };
+
+        for _ in 0..2 {
+            let req = request(Method::POST, "10.0.0.1");
+            assert_eq!(status_of(&app, req).await, StatusCode::OK);
+        }
+        let err = test::try_call_service(&app, request(Method::POST, "10.0.0.1"))
+            .await
+            .err()
+            .expect("third write is limited");
+        let resp = err.error_response();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = resp
+            .headers()
+            .get(header::RETRY_AFTER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=60).contains(&retry_after));
+
+        // Reads and other clients are not affected.
+        let req = request(Method::GET, "10.0.0.1");
+        assert_eq!(status_of(&app, req).await, StatusCode::OK);
+        let req = request(Method::POST, "10.0.0.2");
+        assert_eq!(status_of(&app, req).await, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn upsert_inserts_then_updates() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO students (id, name) VALUES (1, 'Ana')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(Metrics::new().unwrap()))
+                .route("/attendance/upsert", web::put().to(upsert_attendance)),
+        )
+        .await;
+
+        for (status, expected_status, action) in [
+            ("Absent", StatusCode::CREATED, "inserted"),
+            ("Late", StatusCode::OK, "updated"),
+        ] {
+            let req = test::TestRequest::put()
+                .uri("/attendance/upsert")
+                .set_json(serde_json::json!({
+                    "student_id": 1,
+                    "date": "2024-01-15",
+                    "status": status,
+                }))
+                .to_request();
+            authenticate(&req, Role::Staff);
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), expected_status);
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(body["action"], action);
+        }
+
+        let stored = sqlx::query_scalar::<_, String>("SELECT status FROM active_attendance")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(stored, ["Late"]);
+    }
+
+    #[actix_web::test]
+    async fn lenient_dates_are_stored_zero_padded() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO students (id, name) VALUES (1, 'Ana')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(Metrics::new().unwrap()))
+                .route("/attendance/upsert", web::put().to(upsert_attendance)),
+        )
+        .await;
+
+        // Both spellings name the same day, so the second request updates the first record.
+        for (date, expected_status) in [
+            ("2024-1-5", StatusCode::CREATED),
+            ("+2024-01-05", StatusCode::OK),
+        ] {
+            let req = test::TestRequest::put()
+                .uri("/attendance/upsert")
+                .set_json(serde_json::json!({
+                    "student_id": 1,
+                    "date": date,
+                    "status": "Present",
+                }))
+                .to_request();
+            authenticate(&req, Role::Admin);
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), expected_status);
+        }
+
+        let stored = sqlx::query_scalar::<_, String>("SELECT date FROM active_attendance")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(stored, ["2024-01-05"]);
+    }
+
+    #[actix_web::test]
+    async fn reports_count_each_session_separately() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO students (id, name) VALUES (1, 'Ana'), (2, 'Ben')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        // Ana attends both sessions of the 15th, Ben misses both; both attend the 16th's AM.
+        sqlx::query(
+            "INSERT INTO attendance (student_id, date, session, status) VALUES \
+                 (1, '2024-01-15', 'AM', 'Present'), (1, '2024-01-15', 'PM', 'Present'), \
+                 (2, '2024-01-15', 'AM', 'Absent'), (2, '2024-01-15', 'PM', 'Absent'), \
+                 (1, '2024-01-16', 'AM', 'Present'), (2, '2024-01-16', 'AM', 'Present')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .route("/perfect", web::get().to(get_perfect_days))
+                .route("/streak/{id}", web::get().to(get_student_streak))
+                .route("/monthly", web::get().to(get_monthly_report))
+                .route("/quarterly", web::get().to(get_quarterly_report))
+                .route("/heatmap", web::get().to(get_heatmap)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/perfect").to_request();
+        let days: Vec<String> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(days, ["2024-01-16"]);
+
+        let req = test::TestRequest::get().uri("/streak/1").to_request();
+        let streak: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(streak["current_streak"], 3);
+
+        let req = test::TestRequest::get().uri("/monthly").to_request();
+        let months: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            months,
+            serde_json::json!([
+                {"month": "2024-01", "session": "AM", "present_count": 3, "absent_count": 1,
+                 "late_count": 0, "attendance_rate": 0.75},
+                {"month": "2024-01", "session": "PM", "present_count": 1, "absent_count": 1,
+                 "late_count": 0, "attendance_rate": 0.5},
+            ])
+        );
+
+        for uri in ["/quarterly", "/heatmap?year=2024"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let rows: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+            assert!(rows.iter().all(|row| row["session"].is_string()), "{}", uri);
+        }
+    }
+
+    #[actix_web::test]
+    async fn soft_deleted_records_can_be_listed_and_restored() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO students (id, name) VALUES (1, 'Ana')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO attendance (id, student_id, date, status) \
+             VALUES (1, 1, '2024-01-15', 'Present'), (2, 1, '2024-01-16', 'Present')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let repository: Arc<dyn AttendanceRepository + Send + Sync> =
+            Arc::new(SqliteRepository { pool: pool.clone() });
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(repository))
+                .route(
+                    "/attendance/deleted",
+                    web::get().to(list_deleted_attendance),
+                )
+                .route(
+                    "/attendance/{id}/restore",
+                    web::post().to(restore_attendance_record),
+                )
+                .route(
+                    "/attendance/{id}",
+                    web::delete().to(delete_attendance_record),
+                ),
+        )
+        .await;
+        let send = |req: test::TestRequest| {
+            let req = req.to_request();
+            authenticate(&req, Role::Admin);
+            test::call_service(&app, req)
+        };
+
+        for id in [1, 2] {
+            let resp = send(test::TestRequest::delete().uri(&format!("/attendance/{}", id))).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+        let resp = send(test::TestRequest::get().uri("/attendance/deleted")).await;
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["total"], 2);
+
+        let resp = send(test::TestRequest::post().uri("/attendance/1/restore")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = send(test::TestRequest::post().uri("/attendance/1/restore")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // Restoring must not create a second active record for the same date and session.
+        sqlx::query(
+            "INSERT INTO attendance (student_id, date, status) VALUES (1, '2024-01-16', 'Late')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let resp = send(test::TestRequest::post().uri("/attendance/2/restore")).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+        let active = sqlx::query_scalar::<_, i64>("SELECT id FROM active_attendance ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(active, [1, 3]);
+    }
+
+    #[actix_web::test]
+    async fn webhook_signature_is_hex_hmac_sha256() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            webhook_signature("Jefe", b"what do ya want for nothing?").unwrap(),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
 }